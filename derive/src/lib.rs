@@ -0,0 +1,248 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Member, Type};
+
+#[derive(Clone, Copy)]
+enum TargetKind {
+    Text,
+    Count,
+    Time,
+}
+
+struct Target {
+    ident: Ident,
+    kind: TargetKind,
+}
+
+impl Target {
+    fn parse(ident: &Ident) -> syn::Result<Self> {
+        let kind = match ident.to_string().as_str() {
+            "state" | "details" | "large_image" | "large_text" | "small_image" | "small_text"
+            | "party_id" | "match_secret" | "join_secret" | "spectate_secret" => TargetKind::Text,
+            "party_size" | "party_max" => TargetKind::Count,
+            "start" | "end" => TargetKind::Time,
+            _ => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("unknown presence field `{}`", ident),
+                ))
+            }
+        };
+        Ok(Target {
+            ident: ident.clone(),
+            kind,
+        })
+    }
+
+    fn place(&self, presence: &Ident) -> TokenStream {
+        match self.ident.to_string().as_str() {
+            "state" => quote!(#presence.state),
+            "details" => quote!(#presence.details),
+            "large_image" => {
+                quote!(#presence.images.get_or_insert_with(::core::default::Default::default).large_key)
+            }
+            "large_text" => {
+                quote!(#presence.images.get_or_insert_with(::core::default::Default::default).large_text)
+            }
+            "small_image" => {
+                quote!(#presence.images.get_or_insert_with(::core::default::Default::default).small_key)
+            }
+            "small_text" => {
+                quote!(#presence.images.get_or_insert_with(::core::default::Default::default).small_text)
+            }
+            "party_id" => {
+                quote!(#presence.party.get_or_insert_with(::core::default::Default::default).id)
+            }
+            "party_size" => {
+                quote!(#presence.party.get_or_insert_with(::core::default::Default::default).size)
+            }
+            "party_max" => {
+                quote!(#presence.party.get_or_insert_with(::core::default::Default::default).max)
+            }
+            "match_secret" => {
+                quote!(#presence.secrets.get_or_insert_with(::core::default::Default::default).match_)
+            }
+            "join_secret" => {
+                quote!(#presence.secrets.get_or_insert_with(::core::default::Default::default).join)
+            }
+            "spectate_secret" => {
+                quote!(#presence.secrets.get_or_insert_with(::core::default::Default::default).spectate)
+            }
+            "start" => {
+                quote!(#presence.timestamps.get_or_insert_with(::core::default::Default::default).start)
+            }
+            "end" => {
+                quote!(#presence.timestamps.get_or_insert_with(::core::default::Default::default).end)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+struct Mapping {
+    target: Target,
+    format: Option<LitStr>,
+}
+
+fn parse_mappings(attrs: &[syn::Attribute]) -> syn::Result<Vec<Mapping>> {
+    let mut mappings = Vec::new();
+    for attr in attrs {
+        if !attr.path().is_ident("presence") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let ident = meta
+                .path
+                .get_ident()
+                .ok_or_else(|| meta.error("expected a presence field name"))?;
+            let target = Target::parse(ident)?;
+            let format = if meta.input.peek(syn::Token![=]) {
+                Some(meta.value()?.parse::<LitStr>()?)
+            } else {
+                None
+            };
+            mappings.push(Mapping { target, format });
+            Ok(())
+        })?;
+    }
+    Ok(mappings)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn field_value(mapping: &Mapping, value: &TokenStream) -> syn::Result<TokenStream> {
+    Ok(match (mapping.target.kind, &mapping.format) {
+        (TargetKind::Text, Some(format)) => quote!(::std::format!(#format, #value)),
+        (TargetKind::Text, None) => quote!(::std::string::ToString::to_string(#value)),
+        (TargetKind::Count, None) => {
+            quote!(::core::convert::Into::<u32>::into(::core::clone::Clone::clone(#value)))
+        }
+        (TargetKind::Time, None) => quote!(::core::convert::Into::<::discord_rpc::Timestamp>::into(
+            ::core::clone::Clone::clone(#value)
+        )),
+        (_, Some(format)) => {
+            return Err(syn::Error::new(
+                format.span(),
+                format!(
+                    "presence field `{}` doesn't take a format string",
+                    mapping.target.ident
+                ),
+            ))
+        }
+    })
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "ToPresence can only be derived for structs",
+            ))
+        }
+    };
+
+    let presence = Ident::new("presence", Span::mixed_site());
+    let mut assignments = Vec::new();
+
+    for (i, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        };
+        let optional = is_option(&field.ty);
+        for mapping in parse_mappings(&field.attrs)? {
+            let place = mapping.target.place(&presence);
+            if optional {
+                let value = field_value(&mapping, &quote!(value))?;
+                assignments.push(quote! {
+                    #place = self.#member.as_ref().map(|value| #value);
+                });
+            } else {
+                let value = field_value(&mapping, &quote!(&self.#member))?;
+                assignments.push(quote! {
+                    #place = ::core::option::Option::Some(#value);
+                });
+            }
+        }
+    }
+
+    let struct_mappings = parse_mappings(&input.attrs)?;
+    let bindings = if struct_mappings.is_empty() {
+        quote!()
+    } else {
+        let names = match fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|field| field.ident.clone().unwrap())
+                .collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "struct-level presence format strings require named fields",
+                ))
+            }
+        };
+        quote! {
+            #[allow(unused_variables)]
+            let Self { #(#names,)* .. } = self;
+        }
+    };
+    for mapping in &struct_mappings {
+        let place = mapping.target.place(&presence);
+        let format = mapping.format.as_ref().ok_or_else(|| {
+            syn::Error::new(
+                mapping.target.ident.span(),
+                "struct-level presence fields need a format string",
+            )
+        })?;
+        if !matches!(mapping.target.kind, TargetKind::Text) {
+            return Err(syn::Error::new(
+                format.span(),
+                format!(
+                    "presence field `{}` doesn't take a format string",
+                    mapping.target.ident
+                ),
+            ));
+        }
+        assignments.push(quote! {
+            #place = ::core::option::Option::Some(::std::format!(#format));
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::discord_rpc::ToPresence for #name #ty_generics #where_clause {
+            fn to_presence(&self) -> ::discord_rpc::Presence {
+                #bindings
+                let mut #presence = ::discord_rpc::Presence::default();
+                #(#assignments)*
+                #presence
+            }
+        }
+    })
+}
+
+/// Maps a struct to a `Presence`. Fields are mapped with `#[presence(target)]` or
+/// `#[presence(target = "format {}")]`; struct-level `#[presence(target = "{field}")]` format
+/// strings can refer to any named field.
+#[proc_macro_derive(ToPresence, attributes(presence))]
+pub fn derive_to_presence(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
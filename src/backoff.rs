@@ -1,28 +1,76 @@
 use rand::random;
 use std::time::Duration;
 
+/// How the delay between reconnection attempts is randomized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Jitter {
+    /// Doubles the delay after each attempt.
+    None,
+    /// Grows the delay by a random factor between 1 and 3 after each attempt.
+    #[default]
+    Multiplicative,
+    /// Picks a random delay between the minimum and the doubled delay after each attempt.
+    Full,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: Jitter,
+    /// The number of consecutive failed attempts after which the IO thread stops retrying and
+    /// emits a [`DisconnectReason::GaveUp`](crate::DisconnectReason::GaveUp) disconnection;
+    /// `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            min_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            jitter: Jitter::Multiplicative,
+            max_attempts: None,
+        }
+    }
+}
+
 pub struct Backoff {
     pub min: Duration,
     pub max: Duration,
+    pub jitter: Jitter,
     pub current_delay: Duration,
+    ceiling: Duration,
 }
 
 impl Backoff {
-    pub fn new(min: Duration, max: Duration) -> Self {
+    pub fn new(min: Duration, max: Duration, jitter: Jitter) -> Self {
         Backoff {
             min,
             max,
+            jitter,
             current_delay: min,
+            ceiling: min,
         }
     }
 
     pub fn reset(&mut self) {
         self.current_delay = self.min;
+        self.ceiling = self.min;
     }
 
     pub fn next(&mut self) -> Duration {
-        self.current_delay =
-            (self.current_delay + self.current_delay.mul_f32(2.0 * random::<f32>())).min(self.max);
+        self.current_delay = match self.jitter {
+            Jitter::None => self.current_delay * 2,
+            Jitter::Multiplicative => {
+                self.current_delay + self.current_delay.mul_f32(2.0 * random::<f32>())
+            }
+            Jitter::Full => {
+                self.ceiling = (self.ceiling * 2).min(self.max);
+                self.min + (self.ceiling.saturating_sub(self.min)).mul_f32(random::<f32>())
+            }
+        }
+        .min(self.max);
         self.current_delay
     }
 }
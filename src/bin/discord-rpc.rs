@@ -0,0 +1,213 @@
+use discord_rpc::{ActivityType, Event, EventHandlers, Presence, PresenceBuilder, Rpc};
+use std::{
+    env, fs, process,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const USAGE: &str = "\
+Usage: discord-rpc <APP_ID> [OPTIONS]
+
+Connects to Discord and sets the presence, which Discord clears again once this exits unless
+--stay keeps it running.
+
+Options:
+    --json <FILE>             Send the activity in a JSON file instead of the fields below
+    --type <TYPE>             playing, listening, watching or competing
+    --state <TEXT>
+    --details <TEXT>
+    --start <now|UNIX_TIME>
+    --end <UNIX_TIME>
+    --large-image <KEY>
+    --large-text <TEXT>
+    --small-image <KEY>
+    --small-text <TEXT>
+    --party-id <ID>
+    --party-size <SIZE>/<MAX>
+    --button <LABEL> <URL>    Can be given twice
+    --instance                Mark the activity as a specific match or session
+    --timeout <SECONDS>       How long to wait for Discord, 10 by default
+    --stay                    Keep running and print events as JSON lines until killed
+    -h, --help";
+
+struct Args {
+    app_id: String,
+    json: Option<String>,
+    presence: PresenceBuilder,
+    timeout: Duration,
+    stay: bool,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn unix_time(value: &str) -> SystemTime {
+    if value == "now" {
+        return SystemTime::now();
+    }
+    match value.parse() {
+        Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        Err(_) => fail(&format!("invalid timestamp `{}`", value)),
+    }
+}
+
+fn parse_args() -> Args {
+    let mut args = env::args().skip(1);
+    let mut app_id = None;
+    let mut json = None;
+    let mut presence = Presence::builder();
+    let mut timeout = Duration::from_secs(10);
+    let mut stay = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(&format!("missing value for `{}`", arg)))
+        };
+        presence = match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--json" => {
+                json = Some(value());
+                presence
+            }
+            "--type" => presence.kind(match value().as_str() {
+                "playing" => ActivityType::Playing,
+                "listening" => ActivityType::Listening,
+                "watching" => ActivityType::Watching,
+                "competing" => ActivityType::Competing,
+                kind => fail(&format!("unknown activity type `{}`", kind)),
+            }),
+            "--state" => presence.state(value()),
+            "--details" => presence.details(value()),
+            "--start" => presence.start(unix_time(&value())),
+            "--end" => presence.end(unix_time(&value())),
+            "--large-image" => presence.large_image(value()),
+            "--large-text" => presence.large_text(value()),
+            "--small-image" => presence.small_image(value()),
+            "--small-text" => presence.small_text(value()),
+            "--party-id" => presence.party_id(value()),
+            "--party-size" => {
+                let size = value();
+                match size
+                    .split_once('/')
+                    .and_then(|(size, max)| Some((size.parse().ok()?, max.parse().ok()?)))
+                {
+                    Some((size, max)) => presence.party_size(size, max),
+                    None => fail(&format!("invalid party size `{}`", size)),
+                }
+            }
+            "--button" => {
+                let label = value();
+                presence.button(label, value())
+            }
+            "--instance" => presence.instance(true),
+            "--timeout" => {
+                let secs = value();
+                // Deadlines are computed from the timeout, so it has to fit in an `Instant` too
+                match secs
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .filter(|&timeout| Instant::now().checked_add(timeout).is_some())
+                {
+                    Some(value) => timeout = value,
+                    None => fail(&format!("invalid timeout `{}`", secs)),
+                }
+                presence
+            }
+            "--stay" => {
+                stay = true;
+                presence
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`", arg)),
+            _ if app_id.is_none() => {
+                app_id = Some(arg);
+                presence
+            }
+            _ => fail(&format!("unexpected argument `{}`", arg)),
+        };
+    }
+
+    Args {
+        app_id: app_id.unwrap_or_else(|| fail("missing application ID")),
+        json,
+        presence,
+        timeout,
+        stay,
+    }
+}
+
+fn print_event(event: &Event) {
+    if let Ok(event) = serde_json::to_string(event) {
+        println!("{}", event);
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let mut rpc = Rpc::new(args.app_id, EventHandlers::default(), false);
+
+    let deadline = Instant::now() + args.timeout;
+    loop {
+        match rpc.wait_event(Some(deadline.saturating_duration_since(Instant::now()))) {
+            Some(event @ Event::Connected(_)) => {
+                if args.stay {
+                    print_event(&event);
+                }
+                break;
+            }
+            Some(event) => {
+                if args.stay {
+                    print_event(&event);
+                }
+            }
+            None => {
+                eprintln!("error: couldn't connect to Discord");
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut receipt = match &args.json {
+        Some(path) => {
+            let activity = fs::read(path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| {
+                    serde_json::from_slice(&contents).map_err(|err| err.to_string())
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("error: couldn't load {}: {}", path, err);
+                    process::exit(1);
+                });
+            rpc.update_presence_json(activity)
+        }
+        None => match args.presence.build() {
+            Ok(presence) => rpc.update_presence(Some(&presence)),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        },
+    };
+    match receipt.wait_timeout(args.timeout) {
+        Some(Ok(_)) => {}
+        Some(Err(err)) => {
+            eprintln!("error: {} (code {})", err.message, err.code);
+            process::exit(1);
+        }
+        None => {
+            eprintln!("error: Discord didn't respond");
+            process::exit(1);
+        }
+    }
+
+    if args.stay {
+        while let Some(event) = rpc.wait_event(None) {
+            print_event(&event);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use super::{
+    validate_presence, ActivityFlags, ActivityType, Button, Party, PartyPrivacy, Platform,
+    Presence, PresenceError, Timestamp, Timestamps,
+};
+
+/// Builds a [`Presence`] field by field, checking it against Discord's limits in
+/// [`build`](PresenceBuilder::build) instead of letting Discord silently drop it.
+#[derive(Clone, Debug, Default)]
+pub struct PresenceBuilder {
+    presence: Presence,
+}
+
+impl Presence {
+    pub fn builder() -> PresenceBuilder {
+        PresenceBuilder::default()
+    }
+}
+
+impl PresenceBuilder {
+    pub fn kind(mut self, kind: ActivityType) -> Self {
+        self.presence.kind = kind;
+        self
+    }
+
+    pub fn state(mut self, state: impl Into<String>) -> Self {
+        self.presence.state = Some(state.into());
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.presence.details = Some(details.into());
+        self
+    }
+
+    pub fn timestamps(mut self, timestamps: Timestamps) -> Self {
+        self.presence.timestamps = Some(timestamps);
+        self
+    }
+
+    pub fn start(mut self, start: impl Into<Timestamp>) -> Self {
+        self.presence
+            .timestamps
+            .get_or_insert_with(Default::default)
+            .start = Some(start.into());
+        self
+    }
+
+    pub fn end(mut self, end: impl Into<Timestamp>) -> Self {
+        self.presence
+            .timestamps
+            .get_or_insert_with(Default::default)
+            .end = Some(end.into());
+        self
+    }
+
+    pub fn large_image(mut self, key: impl Into<String>) -> Self {
+        self.presence
+            .images
+            .get_or_insert_with(Default::default)
+            .large_key = Some(key.into());
+        self
+    }
+
+    pub fn large_text(mut self, text: impl Into<String>) -> Self {
+        self.presence
+            .images
+            .get_or_insert_with(Default::default)
+            .large_text = Some(text.into());
+        self
+    }
+
+    pub fn small_image(mut self, key: impl Into<String>) -> Self {
+        self.presence
+            .images
+            .get_or_insert_with(Default::default)
+            .small_key = Some(key.into());
+        self
+    }
+
+    pub fn small_text(mut self, text: impl Into<String>) -> Self {
+        self.presence
+            .images
+            .get_or_insert_with(Default::default)
+            .small_text = Some(text.into());
+        self
+    }
+
+    pub fn party_id(mut self, id: impl Into<String>) -> Self {
+        self.presence.party.get_or_insert_with(Default::default).id = Some(id.into());
+        self
+    }
+
+    pub fn party_size(mut self, size: u32, max: u32) -> Self {
+        let party = self.presence.party.get_or_insert_with(Default::default);
+        party.size = Some(size);
+        party.max = Some(max);
+        self
+    }
+
+    pub fn party(mut self, party: Party) -> Self {
+        self.presence.party = Some(party);
+        self
+    }
+
+    pub fn party_privacy(mut self, privacy: PartyPrivacy) -> Self {
+        self.presence
+            .party
+            .get_or_insert_with(Default::default)
+            .privacy = Some(privacy);
+        self
+    }
+
+    pub fn match_secret(mut self, secret: impl Into<String>) -> Self {
+        self.presence
+            .secrets
+            .get_or_insert_with(Default::default)
+            .match_ = Some(secret.into());
+        self
+    }
+
+    pub fn join_secret(mut self, secret: impl Into<String>) -> Self {
+        self.presence
+            .secrets
+            .get_or_insert_with(Default::default)
+            .join = Some(secret.into());
+        self
+    }
+
+    pub fn spectate_secret(mut self, secret: impl Into<String>) -> Self {
+        self.presence
+            .secrets
+            .get_or_insert_with(Default::default)
+            .spectate = Some(secret.into());
+        self
+    }
+
+    pub fn button(mut self, label: impl Into<String>, url: impl Into<String>) -> Self {
+        self.presence.buttons.push(Button {
+            label: label.into(),
+            url: url.into(),
+        });
+        self
+    }
+
+    pub fn instance(mut self, instance: bool) -> Self {
+        self.presence.instance = instance;
+        self
+    }
+
+    pub fn flags(mut self, flags: ActivityFlags) -> Self {
+        self.presence.flags |= flags;
+        self
+    }
+
+    pub fn supported_platform(mut self, platform: Platform) -> Self {
+        self.presence.supported_platforms.insert(platform);
+        self
+    }
+
+    pub fn build(self) -> Result<Presence, PresenceError> {
+        validate_presence(&self.presence)?;
+        Ok(self.presence)
+    }
+}
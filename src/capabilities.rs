@@ -0,0 +1,25 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Capability {
+    #[default]
+    Unknown,
+    Available,
+    Rejected,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub join: Capability,
+    pub spectate: Capability,
+    pub join_request: Capability,
+}
+
+impl Capabilities {
+    pub(crate) fn for_event_mut(&mut self, event: &str) -> Option<&mut Capability> {
+        match event {
+            "ACTIVITY_JOIN" => Some(&mut self.join),
+            "ACTIVITY_SPECTATE" => Some(&mut self.spectate),
+            "ACTIVITY_JOIN_REQUEST" => Some(&mut self.join_request),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use super::{Event, EventHandlers, JoinReply, Presence, Receipt, Rpc, Snowflake, Subscription};
+
+pub trait RpcClient {
+    fn update_presence(&mut self, presence: Option<&Presence>) -> Receipt;
+    fn reply_to_join_request(&mut self, user_id: Snowflake, reply: JoinReply) -> Receipt;
+    fn modify_handlers(&mut self, f: &mut dyn FnMut(&mut EventHandlers));
+    fn subscriptions(&self) -> Vec<Subscription>;
+    fn check_events(&mut self);
+    fn try_recv_event(&mut self) -> Option<Event>;
+}
+
+impl RpcClient for Rpc {
+    fn update_presence(&mut self, presence: Option<&Presence>) -> Receipt {
+        Rpc::update_presence(self, presence)
+    }
+
+    fn reply_to_join_request(&mut self, user_id: Snowflake, reply: JoinReply) -> Receipt {
+        Rpc::reply_to_join_request(self, user_id, reply)
+    }
+
+    fn modify_handlers(&mut self, f: &mut dyn FnMut(&mut EventHandlers)) {
+        Rpc::modify_handlers(self, f);
+    }
+
+    fn subscriptions(&self) -> Vec<Subscription> {
+        Rpc::subscriptions(self)
+    }
+
+    fn check_events(&mut self) {
+        Rpc::check_events(self);
+    }
+
+    fn try_recv_event(&mut self) -> Option<Event> {
+        Rpc::try_recv_event(self)
+    }
+}
@@ -6,10 +6,21 @@ pub use unix::*;
 mod windows;
 #[cfg(target_family = "windows")]
 pub use windows::*;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "websocket")]
+pub use websocket::*;
 
-use super::{messages, User};
+use super::messages;
 use serde::{Deserialize, Serialize};
-use std::io::{self, Read, Write};
+use std::{
+    env, error, fmt,
+    io::{self, Read, Write},
+    ops::Range,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
 
 pub mod opcode {
     pub const HANDSHAKE: u32 = 0;
@@ -19,17 +30,417 @@ pub mod opcode {
     pub const PONG: u32 = 4;
 }
 
-pub struct Connection {
-    connection: Option<BaseConnection>,
+pub type ConnectHandler = Box<dyn FnMut(messages::HandshakeReplyData)>;
+pub type DisconnectHandler = Box<dyn FnMut(&DisconnectReason)>;
+
+/// A directory searched for Discord's IPC socket on Unix, relative to the runtime directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpcLocation {
+    /// `$XDG_RUNTIME_DIR/discord-ipc-N`, used by native installs.
+    Default,
+    /// `$XDG_RUNTIME_DIR/app/com.discordapp.Discord/discord-ipc-N`, used by the Flatpak.
+    Flatpak,
+    /// `$XDG_RUNTIME_DIR/snap.discord/discord-ipc-N`, used by the Snap.
+    Snap,
+}
+
+impl IpcLocation {
+    pub fn subdirectory(self) -> &'static str {
+        match self {
+            IpcLocation::Default => "",
+            IpcLocation::Flatpak => "/app/com.discordapp.Discord",
+            IpcLocation::Snap => "/snap.discord",
+        }
+    }
+}
+
+/// The environment variable that, when set, overrides the IPC socket or pipe path.
+pub const IPC_PATH_VAR: &str = "DISCORD_IPC_PATH";
+
+#[derive(Clone, Debug)]
+pub struct IpcConfig {
+    /// An explicit socket or pipe path to connect to, skipping the search entirely. Takes
+    /// precedence over `DISCORD_IPC_PATH`.
+    pub path: Option<PathBuf>,
+    /// The locations to try, in order; ignored on Windows, where Discord always uses named pipes.
+    pub search_order: Vec<IpcLocation>,
+    /// The `discord-ipc-N` indices to try in each location.
+    pub pipe_indices: Range<u32>,
+    /// The largest frame accepted from Discord; a longer length field is treated as corruption
+    /// and closes the connection.
+    pub max_frame_size: usize,
+    /// Limits on connecting, writing and the handshake, see [`IoTimeouts`].
+    pub timeouts: IoTimeouts,
+}
+
+impl IpcConfig {
+    pub(crate) fn explicit_path(&self) -> Option<PathBuf> {
+        self.path.clone().or_else(|| {
+            env::var_os(IPC_PATH_VAR)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+}
+
+impl Default for IpcConfig {
+    fn default() -> Self {
+        IpcConfig {
+            path: None,
+            search_order: vec![
+                IpcLocation::Default,
+                IpcLocation::Flatpak,
+                IpcLocation::Snap,
+            ],
+            pipe_indices: 0..10,
+            max_frame_size: 1024 * 1024,
+            timeouts: IoTimeouts::default(),
+        }
+    }
+}
+
+/// How long individual stream operations may take before they're given up on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoTimeouts {
+    /// How long establishing a connection may take; only connections over the network, i.e.
+    /// through the WebSocket transport, can take noticeably long.
+    pub connect: Duration,
+    /// How long a frame may take to be written while Discord isn't reading, after which the
+    /// connection is closed and retried.
+    pub write: Duration,
+    /// How long Discord gets to answer the handshake before the connection is closed and
+    /// retried, for clients that accept connections before they're ready to serve them.
+    pub handshake: Duration,
+}
+
+impl Default for IoTimeouts {
+    fn default() -> Self {
+        IoTimeouts {
+            connect: Duration::from_millis(200),
+            write: Duration::from_secs(5),
+            handshake: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Retries `write` on a non-blocking stream while it would block, failing with
+/// `io::ErrorKind::TimedOut` once `timeout` has passed.
+pub(crate) fn write_with_timeout(
+    timeout: Duration,
+    mut write: impl FnMut() -> io::Result<usize>,
+) -> io::Result<usize> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match write() {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Write timed out"));
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// How the IO thread checks that Discord is still responsive: after `interval` without receiving
+/// anything it sends a PING, and if nothing arrives within `timeout` it drops the connection and
+/// reconnects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Keepalive {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A byte stream to a Discord client. Reads must not block: when no data is pending, they fail
+/// with `io::ErrorKind::WouldBlock`. Writes that can't complete within the write timeout in
+/// [`IpcConfig::timeouts`] should fail with `io::ErrorKind::TimedOut`.
+pub trait Transport: Read + Write {
+    /// The `discord-ipc-N` index this transport was opened on, if it was found by searching.
+    fn pipe_index(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Opens [`Transport`]s; called from the IO thread on every connection attempt.
+pub trait Connector: Send + 'static {
+    type Transport: Transport;
+
+    fn connect(&mut self, config: &IpcConfig) -> io::Result<Self::Transport>;
+}
+
+/// Connects to the local Discord client over its platform IPC socket or named pipe.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IpcConnector;
+
+impl Connector for IpcConnector {
+    type Transport = BaseConnection;
+
+    fn connect(&mut self, config: &IpcConfig) -> io::Result<BaseConnection> {
+        BaseConnection::open(config)
+    }
+}
+
+impl Transport for BaseConnection {
+    fn pipe_index(&self) -> Option<u32> {
+        self.index
+    }
+}
+
+/// Connects with `primary`, and with `fallback` only when that fails.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FallbackConnector<P, F> {
+    pub primary: P,
+    pub fallback: F,
+}
+
+pub enum FallbackTransport<P, F> {
+    Primary(P),
+    Fallback(F),
+}
+
+impl<P: Connector, F: Connector> Connector for FallbackConnector<P, F> {
+    type Transport = FallbackTransport<P::Transport, F::Transport>;
+
+    fn connect(&mut self, config: &IpcConfig) -> io::Result<Self::Transport> {
+        match self.primary.connect(config) {
+            Ok(transport) => Ok(FallbackTransport::Primary(transport)),
+            Err(_) => self
+                .fallback
+                .connect(config)
+                .map(FallbackTransport::Fallback),
+        }
+    }
+}
+
+impl<P: Read, F: Read> Read for FallbackTransport<P, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            FallbackTransport::Primary(transport) => transport.read(buf),
+            FallbackTransport::Fallback(transport) => transport.read(buf),
+        }
+    }
+}
+
+impl<P: Write, F: Write> Write for FallbackTransport<P, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FallbackTransport::Primary(transport) => transport.write(buf),
+            FallbackTransport::Fallback(transport) => transport.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FallbackTransport::Primary(transport) => transport.flush(),
+            FallbackTransport::Fallback(transport) => transport.flush(),
+        }
+    }
+}
+
+impl<P: Transport, F: Transport> Transport for FallbackTransport<P, F> {
+    fn pipe_index(&self) -> Option<u32> {
+        match self {
+            FallbackTransport::Primary(transport) => transport.pipe_index(),
+            FallbackTransport::Fallback(transport) => transport.pipe_index(),
+        }
+    }
+}
+
+pub struct Connection<C: Connector = IpcConnector> {
+    connector: C,
+    connection: Option<C::Transport>,
     is_connected: bool,
-    pub on_connect: Option<Box<dyn FnMut(Option<User>)>>,
-    pub on_disconnect: Option<Box<dyn FnMut(Option<&StreamError>)>>,
+    handshake_sent_at: Option<Instant>,
+    pub on_connect: Option<ConnectHandler>,
+    pub on_disconnect: Option<DisconnectHandler>,
     pub app_id: String,
+    pub ipc_config: IpcConfig,
+    pub traffic: TrafficStats,
+    read_buffer: Vec<u8>,
+}
+
+/// What a [`StreamError`] is about: an error noticed locally, a close code Discord sent along
+/// with a CLOSE frame, or the code of an `ERROR` response. Serialized as the bare number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum ErrorCode {
+    PipeClosed,
+    ReadCorrupt,
+    HandshakeFailed,
+    WriteFailed,
+    ValidationFailed,
+    NoResponse,
+    ShutdownTimeout,
+    PingTimeout,
+    LoadFailed,
+    HandshakeTimeout,
+    WriteTimeout,
+
+    CloseNormal,
+    CloseAbnormal,
+    InvalidClientId,
+    InvalidOrigin,
+    RateLimited,
+    TokenRevoked,
+    InvalidVersion,
+    InvalidEncoding,
+
+    /// The code of an `ERROR` response to a command, numbered independently of close codes, see
+    /// [`RpcErrorCode`](crate::RpcErrorCode). Never produced when deserializing.
+    Rpc(u32),
+    Other(u32),
+}
+
+impl ErrorCode {
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            1 => ErrorCode::PipeClosed,
+            2 => ErrorCode::ReadCorrupt,
+            3 => ErrorCode::HandshakeFailed,
+            4 => ErrorCode::WriteFailed,
+            5 => ErrorCode::ValidationFailed,
+            6 => ErrorCode::NoResponse,
+            7 => ErrorCode::ShutdownTimeout,
+            8 => ErrorCode::PingTimeout,
+            9 => ErrorCode::LoadFailed,
+            10 => ErrorCode::HandshakeTimeout,
+            11 => ErrorCode::WriteTimeout,
+            1000 => ErrorCode::CloseNormal,
+            1006 => ErrorCode::CloseAbnormal,
+            4000 => ErrorCode::InvalidClientId,
+            4001 => ErrorCode::InvalidOrigin,
+            4002 => ErrorCode::RateLimited,
+            4003 => ErrorCode::TokenRevoked,
+            4004 => ErrorCode::InvalidVersion,
+            4005 => ErrorCode::InvalidEncoding,
+            _ => ErrorCode::Other(code),
+        }
+    }
+
+    pub fn code(self) -> u32 {
+        match self {
+            ErrorCode::PipeClosed => 1,
+            ErrorCode::ReadCorrupt => 2,
+            ErrorCode::HandshakeFailed => 3,
+            ErrorCode::WriteFailed => 4,
+            ErrorCode::ValidationFailed => 5,
+            ErrorCode::NoResponse => 6,
+            ErrorCode::ShutdownTimeout => 7,
+            ErrorCode::PingTimeout => 8,
+            ErrorCode::LoadFailed => 9,
+            ErrorCode::HandshakeTimeout => 10,
+            ErrorCode::WriteTimeout => 11,
+            ErrorCode::CloseNormal => 1000,
+            ErrorCode::CloseAbnormal => 1006,
+            ErrorCode::InvalidClientId => 4000,
+            ErrorCode::InvalidOrigin => 4001,
+            ErrorCode::RateLimited => 4002,
+            ErrorCode::TokenRevoked => 4003,
+            ErrorCode::InvalidVersion => 4004,
+            ErrorCode::InvalidEncoding => 4005,
+            ErrorCode::Rpc(code) | ErrorCode::Other(code) => code,
+        }
+    }
+
+    /// Whether the error was noticed on this side of the connection rather than sent by Discord.
+    pub fn is_local(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::PipeClosed
+                | ErrorCode::ReadCorrupt
+                | ErrorCode::HandshakeFailed
+                | ErrorCode::WriteFailed
+                | ErrorCode::ValidationFailed
+                | ErrorCode::NoResponse
+                | ErrorCode::ShutdownTimeout
+                | ErrorCode::PingTimeout
+                | ErrorCode::LoadFailed
+                | ErrorCode::HandshakeTimeout
+                | ErrorCode::WriteTimeout
+        )
+    }
+
+    /// Whether this is one of the close codes Discord sends in a CLOSE frame.
+    pub fn is_close_code(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::CloseNormal
+                | ErrorCode::CloseAbnormal
+                | ErrorCode::InvalidClientId
+                | ErrorCode::InvalidOrigin
+                | ErrorCode::RateLimited
+                | ErrorCode::TokenRevoked
+                | ErrorCode::InvalidVersion
+                | ErrorCode::InvalidEncoding
+        )
+    }
+}
+
+impl From<u32> for ErrorCode {
+    fn from(code: u32) -> Self {
+        ErrorCode::from_code(code)
+    }
+}
+
+impl From<ErrorCode> for u32 {
+    fn from(code: ErrorCode) -> Self {
+        code.code()
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
 }
 
-pub mod error_code {
-    pub const PIPE_CLOSED: u8 = 1;
-    pub const READ_CORRUPT: u8 = 2;
+#[derive(Clone, Debug, Serialize)]
+pub enum DisconnectReason {
+    Shutdown,
+    /// The connection was closed to reconnect with another client ID.
+    AppIdChanged,
+    /// The connection was closed because the client was disabled, and reopens once re-enabled.
+    Disabled,
+    PipeClosed,
+    Closed(Option<StreamError>),
+    HandshakeFailed(StreamError),
+    Local(StreamError),
+    /// The reconnection policy's maximum number of attempts was reached.
+    GaveUp {
+        attempts: u32,
+    },
+}
+
+impl DisconnectReason {
+    pub fn error(&self) -> Option<&StreamError> {
+        match self {
+            DisconnectReason::Shutdown
+            | DisconnectReason::AppIdChanged
+            | DisconnectReason::Disabled
+            | DisconnectReason::PipeClosed
+            | DisconnectReason::GaveUp { .. } => None,
+            DisconnectReason::Closed(error) => error.as_ref(),
+            DisconnectReason::HandshakeFailed(error) | DisconnectReason::Local(error) => {
+                Some(error)
+            }
+        }
+    }
+
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            DisconnectReason::Shutdown | DisconnectReason::GaveUp { .. } => false,
+            _ => self.error().is_none_or(StreamError::is_recoverable),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -37,15 +448,74 @@ pub enum OpenError {
     Stream(io::Error),
     HandshakeSend(JsonWriteError),
     HandshakeReceive(JsonReadError),
-    InvalidHandshake(messages::HandshakeReply),
+    InvalidHandshake { command: String, event: String },
+    ConnectTimeout,
+    HandshakeTimeout,
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenError::Stream(_) => f.write_str("couldn't open the IPC connection"),
+            OpenError::HandshakeSend(_) => f.write_str("couldn't send the handshake"),
+            OpenError::HandshakeReceive(_) => f.write_str("couldn't receive the handshake reply"),
+            OpenError::InvalidHandshake { command, event } => {
+                write!(f, "unexpected handshake reply: {} {}", command, event)
+            }
+            OpenError::ConnectTimeout => f.write_str("timed out opening the connection"),
+            OpenError::HandshakeTimeout => f.write_str("no handshake reply received in time"),
+        }
+    }
+}
+
+impl error::Error for OpenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OpenError::Stream(err) => Some(err),
+            OpenError::HandshakeSend(err) => Some(err),
+            OpenError::HandshakeReceive(err) => Some(err),
+            OpenError::InvalidHandshake { .. }
+            | OpenError::ConnectTimeout
+            | OpenError::HandshakeTimeout => None,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StreamError {
     pub message: String,
-    pub code: u8,
+    pub code: ErrorCode,
+}
+
+impl StreamError {
+    pub fn is_client_missing(&self) -> bool {
+        self.code == ErrorCode::PipeClosed
+    }
+
+    pub fn is_rate_limited(&self) -> bool {
+        self.code == ErrorCode::RateLimited
+    }
+
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self.code,
+            ErrorCode::InvalidClientId
+                | ErrorCode::InvalidOrigin
+                | ErrorCode::TokenRevoked
+                | ErrorCode::InvalidVersion
+                | ErrorCode::InvalidEncoding
+        )
+    }
 }
 
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl error::Error for StreamError {}
+
 #[derive(Debug)]
 pub enum JsonReadError {
     Json(serde_json::Error),
@@ -54,50 +524,154 @@ pub enum JsonReadError {
     Disconnected,
 }
 
+impl fmt::Display for JsonReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonReadError::Json(_) => f.write_str("couldn't parse the received frame"),
+            JsonReadError::Io(_) => f.write_str("couldn't read from the IPC connection"),
+            JsonReadError::Stream(Some(_)) => f.write_str("the IPC connection was closed"),
+            JsonReadError::Stream(None) => {
+                f.write_str("the IPC connection was closed without a reason")
+            }
+            JsonReadError::Disconnected => f.write_str("not connected"),
+        }
+    }
+}
+
+impl error::Error for JsonReadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JsonReadError::Json(err) => Some(err),
+            JsonReadError::Io(err) => Some(err),
+            JsonReadError::Stream(err) => err.as_ref().map(|err| err as _),
+            JsonReadError::Disconnected => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum RawWriteError {
     Io(io::Error),
+    TimedOut,
     Disconnected,
 }
 
+impl fmt::Display for RawWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawWriteError::Io(_) => f.write_str("couldn't write to the IPC connection"),
+            RawWriteError::TimedOut => f.write_str("timed out writing to the IPC connection"),
+            RawWriteError::Disconnected => f.write_str("not connected"),
+        }
+    }
+}
+
+impl error::Error for RawWriteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RawWriteError::Io(err) => Some(err),
+            RawWriteError::TimedOut | RawWriteError::Disconnected => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum JsonWriteError {
     Json(serde_json::Error),
     Raw(RawWriteError),
 }
 
+impl fmt::Display for JsonWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonWriteError::Json(_) => f.write_str("couldn't serialize the frame"),
+            JsonWriteError::Raw(_) => f.write_str("couldn't send the frame"),
+        }
+    }
+}
+
+impl error::Error for JsonWriteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JsonWriteError::Json(err) => Some(err),
+            JsonWriteError::Raw(err) => Some(err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrafficStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub frames_sent: u64,
+    pub frames_received: u64,
+    pub last_frame_received_at: Option<Instant>,
+}
+
+impl TrafficStats {
+    fn record_sent(&mut self, len: usize) {
+        self.frames_sent += 1;
+        self.bytes_sent += 8 + len as u64;
+    }
+
+    fn record_received(&mut self, len: usize) {
+        self.frames_received += 1;
+        self.bytes_received += 8 + len as u64;
+        self.last_frame_received_at = Some(Instant::now());
+    }
+}
+
 fn write_raw_message(
-    connection: &mut BaseConnection,
+    connection: &mut impl Write,
+    traffic: &mut TrafficStats,
     opcode: u32,
     message: &[u8],
 ) -> Result<(), RawWriteError> {
+    let map_err = |err: io::Error| match err.kind() {
+        io::ErrorKind::TimedOut => RawWriteError::TimedOut,
+        _ => RawWriteError::Io(err),
+    };
     connection
         .write_all(&opcode.to_le_bytes())
-        .map_err(RawWriteError::Io)?;
+        .map_err(map_err)?;
     connection
         .write_all(&(message.len() as u32).to_le_bytes())
-        .map_err(RawWriteError::Io)?;
-    connection.write_all(message).map_err(RawWriteError::Io)?;
+        .map_err(map_err)?;
+    connection.write_all(message).map_err(map_err)?;
+    traffic.record_sent(message.len());
     Ok(())
 }
 
+fn write_timeout_error() -> StreamError {
+    StreamError {
+        message: "Write timed out".to_string(),
+        code: ErrorCode::WriteTimeout,
+    }
+}
+
 fn write_json_message<T: Serialize>(
-    connection: &mut BaseConnection,
+    connection: &mut impl Write,
+    traffic: &mut TrafficStats,
     opcode: u32,
     message: &T,
 ) -> Result<(), JsonWriteError> {
     let message = serde_json::to_vec(message).map_err(JsonWriteError::Json)?;
-    write_raw_message(connection, opcode, &message).map_err(JsonWriteError::Raw)
+    write_raw_message(connection, traffic, opcode, &message).map_err(JsonWriteError::Raw)
 }
 
-impl Connection {
-    pub fn new(app_id: String) -> Self {
+impl<C: Connector> Connection<C> {
+    pub fn new(connector: C, app_id: String) -> Self {
         Connection {
+            connector,
             connection: None,
             is_connected: false,
+            handshake_sent_at: None,
             on_connect: None,
             on_disconnect: None,
             app_id,
+            ipc_config: IpcConfig::default(),
+            traffic: TrafficStats::default(),
+            read_buffer: Vec::new(),
         }
     }
 
@@ -111,17 +685,43 @@ impl Connection {
                 .map_err(OpenError::HandshakeReceive)?
             {
                 if handshake.command != "DISPATCH" || handshake.event != "READY" {
-                    return Err(OpenError::InvalidHandshake(handshake));
+                    self.close_with_reason(DisconnectReason::HandshakeFailed(StreamError {
+                        message: format!(
+                            "Unexpected handshake reply: {} {}",
+                            handshake.command, handshake.event
+                        ),
+                        code: ErrorCode::HandshakeFailed,
+                    }));
+                    return Err(OpenError::InvalidHandshake {
+                        command: handshake.command,
+                        event: handshake.event,
+                    });
                 }
                 self.is_connected = true;
                 if let Some(on_connect) = &mut self.on_connect {
-                    on_connect(handshake.data.user);
+                    on_connect(handshake.data);
                 }
+            } else if self
+                .handshake_sent_at
+                .is_some_and(|sent_at| sent_at.elapsed() >= self.ipc_config.timeouts.handshake)
+            {
+                self.close_with_reason(DisconnectReason::HandshakeFailed(StreamError {
+                    message: "No handshake reply received".to_string(),
+                    code: ErrorCode::HandshakeTimeout,
+                }));
+                return Err(OpenError::HandshakeTimeout);
             }
         } else {
-            let mut connection = BaseConnection::open().map_err(OpenError::Stream)?;
+            let mut connection =
+                self.connector
+                    .connect(&self.ipc_config)
+                    .map_err(|err| match err.kind() {
+                        io::ErrorKind::TimedOut => OpenError::ConnectTimeout,
+                        _ => OpenError::Stream(err),
+                    })?;
             write_json_message(
                 &mut connection,
+                &mut self.traffic,
                 opcode::HANDSHAKE,
                 &messages::Handshake {
                     version: 1,
@@ -130,15 +730,38 @@ impl Connection {
             )
             .map_err(OpenError::HandshakeSend)?;
             self.connection = Some(connection);
+            self.handshake_sent_at = Some(Instant::now());
         }
         Ok(())
     }
 
-    fn close_with_error(&mut self, error: Option<&StreamError>) {
+    fn close_with_reason(&mut self, reason: DisconnectReason) {
         self.connection = None;
         self.is_connected = false;
         if let Some(on_disconnect) = &mut self.on_disconnect {
-            on_disconnect(error);
+            on_disconnect(&reason);
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.close_gracefully(DisconnectReason::Shutdown);
+    }
+
+    /// Like [`close`](Self::close), but reports `reason` to the disconnect callback.
+    pub fn close_gracefully(&mut self, reason: DisconnectReason) {
+        if let Some(connection) = &mut self.connection {
+            if self.is_connected {
+                let _ = write_json_message(
+                    connection,
+                    &mut self.traffic,
+                    opcode::CLOSE,
+                    &StreamError {
+                        message: "Shutdown".to_string(),
+                        code: ErrorCode::CloseNormal,
+                    },
+                );
+            }
+            self.close_with_reason(reason);
         }
     }
 
@@ -146,6 +769,16 @@ impl Connection {
         self.is_connected
     }
 
+    /// Whether the transport is open but Discord hasn't answered the handshake yet.
+    pub fn is_handshaking(&self) -> bool {
+        self.connection.is_some() && !self.is_connected
+    }
+
+    /// The `discord-ipc-N` index of the open connection, if it was found by searching.
+    pub fn pipe_index(&self) -> Option<u32> {
+        self.connection.as_ref().and_then(Transport::pipe_index)
+    }
+
     pub fn read_json<T: for<'a> Deserialize<'a>>(&mut self) -> Result<Option<T>, JsonReadError> {
         let connection = self
             .connection
@@ -156,12 +789,21 @@ impl Connection {
             if let Err(err) = connection.read_exact(&mut header) {
                 match err.kind() {
                     io::ErrorKind::WouldBlock => return Ok(None),
+                    // Raised by transports that reject malformed data before framing it
+                    io::ErrorKind::InvalidData => {
+                        let error = StreamError {
+                            message: err.to_string(),
+                            code: ErrorCode::ReadCorrupt,
+                        };
+                        self.close_with_reason(DisconnectReason::Local(error.clone()));
+                        return Err(JsonReadError::Stream(Some(error)));
+                    }
                     _ => {
                         let error = StreamError {
                             message: "Pipe closed".to_string(),
-                            code: error_code::PIPE_CLOSED,
+                            code: ErrorCode::PipeClosed,
                         };
-                        self.close_with_error(Some(&error));
+                        self.close_with_reason(DisconnectReason::PipeClosed);
                         return Err(JsonReadError::Stream(Some(error)));
                     }
                 }
@@ -170,36 +812,57 @@ impl Connection {
             let opcode = u32::from_le_bytes((&header[0..4]).try_into().unwrap());
             let len = u32::from_le_bytes((&header[4..8]).try_into().unwrap());
 
-            let mut message = Vec::new();
+            if len as usize > self.ipc_config.max_frame_size {
+                let error = StreamError {
+                    message: format!("Frame length {} exceeds the maximum", len),
+                    code: ErrorCode::ReadCorrupt,
+                };
+                self.close_with_reason(DisconnectReason::Local(error.clone()));
+                return Err(JsonReadError::Stream(Some(error)));
+            }
+
+            // Reuse the buffer's allocation across frames
+            let message = &mut self.read_buffer;
+            message.clear();
             if len != 0 {
                 message.resize(len as usize, 0);
-                if connection.read_exact(&mut message).is_err() {
+                if connection.read_exact(message).is_err() {
                     let error = StreamError {
                         message: "Partial data in frame".to_string(),
-                        code: error_code::READ_CORRUPT,
+                        code: ErrorCode::ReadCorrupt,
                     };
-                    self.close_with_error(Some(&error));
+                    self.close_with_reason(DisconnectReason::Local(error.clone()));
                     return Err(JsonReadError::Stream(Some(error)));
                 }
             }
+            self.traffic.record_received(message.len());
 
             match opcode {
                 opcode::CLOSE => {
-                    let error = serde_json::from_slice::<StreamError>(&message).ok();
-                    self.close_with_error(error.as_ref());
+                    let error = serde_json::from_slice::<StreamError>(message).ok();
+                    self.close_with_reason(DisconnectReason::Closed(error.clone()));
                     return Err(JsonReadError::Stream(error));
                 }
-                
+
                 opcode::FRAME => {
-                    return serde_json::from_slice(&message).map_err(JsonReadError::Json);
+                    return serde_json::from_slice(message).map_err(JsonReadError::Json);
                 }
 
                 opcode::PING => {
-                    if let Err(RawWriteError::Io(err)) =
-                        write_raw_message(connection, opcode::PONG, &[])
-                    {
-                        self.close_with_error(None);
-                        return Err(JsonReadError::Io(err));
+                    match write_raw_message(connection, &mut self.traffic, opcode::PONG, &[]) {
+                        Err(RawWriteError::Io(err)) => {
+                            self.close_with_reason(DisconnectReason::Local(StreamError {
+                                message: err.to_string(),
+                                code: ErrorCode::WriteFailed,
+                            }));
+                            return Err(JsonReadError::Io(err));
+                        }
+                        Err(RawWriteError::TimedOut) => {
+                            let error = write_timeout_error();
+                            self.close_with_reason(DisconnectReason::Local(error.clone()));
+                            return Err(JsonReadError::Stream(Some(error)));
+                        }
+                        _ => {}
                     }
                 }
 
@@ -208,18 +871,46 @@ impl Connection {
                 _ => {
                     let error = StreamError {
                         message: "Bad frame".to_string(),
-                        code: error_code::READ_CORRUPT,
+                        code: ErrorCode::ReadCorrupt,
                     };
-                    self.close_with_error(Some(&error));
+                    self.close_with_reason(DisconnectReason::Local(error.clone()));
                     return Err(JsonReadError::Stream(Some(error)));
                 }
             }
         }
     }
 
+    /// Drops the connection when a write timed out, as the frame may have been cut short.
+    fn check_write(&mut self, result: Result<(), RawWriteError>) -> Result<(), RawWriteError> {
+        if let Err(RawWriteError::TimedOut) = result {
+            self.close_with_reason(DisconnectReason::Local(write_timeout_error()));
+        }
+        result
+    }
+
+    pub fn ping(&mut self) -> Result<(), RawWriteError> {
+        if let Some(connection) = &mut self.connection {
+            let result = write_raw_message(connection, &mut self.traffic, opcode::PING, &[]);
+            self.check_write(result)
+        } else {
+            Err(RawWriteError::Disconnected)
+        }
+    }
+
+    /// Drops a connection that stopped answering PINGs, without attempting a clean close.
+    pub fn close_unresponsive(&mut self) {
+        if self.connection.is_some() {
+            self.close_with_reason(DisconnectReason::Local(StreamError {
+                message: "No response to PING".to_string(),
+                code: ErrorCode::PingTimeout,
+            }));
+        }
+    }
+
     pub fn write_raw(&mut self, message: &[u8]) -> Result<(), RawWriteError> {
         if let Some(connection) = &mut self.connection {
-            write_raw_message(connection, opcode::FRAME, message)
+            let result = write_raw_message(connection, &mut self.traffic, opcode::FRAME, message);
+            self.check_write(result)
         } else {
             Err(RawWriteError::Disconnected)
         }
@@ -1,27 +1,47 @@
+use super::{write_with_timeout, IpcConfig};
 use std::{
     env,
     ffi::OsStr,
     io::{self, Read, Write},
     os::unix::net::UnixStream,
+    path::Path,
+    time::Duration,
 };
 
 pub struct BaseConnection {
     stream: UnixStream,
+    write_timeout: Duration,
+    pub index: Option<u32>,
 }
 
 impl BaseConnection {
-    pub fn open() -> io::Result<Self> {
+    fn connect(path: impl AsRef<Path>, config: &IpcConfig) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        let _ = stream.set_nonblocking(true);
+        Ok(BaseConnection {
+            stream,
+            write_timeout: config.timeouts.write,
+            index: None,
+        })
+    }
+
+    pub fn open(config: &IpcConfig) -> io::Result<Self> {
+        if let Some(path) = config.explicit_path() {
+            return Self::connect(path, config);
+        }
         let temp_path = env::var_os("XDG_RUNTIME_DIR")
             .or_else(|| env::var_os("TMPDIR"))
             .or_else(|| env::var_os("TMP"))
             .or_else(|| env::var_os("TEMP"))
             .unwrap_or_else(|| OsStr::new("/tmp").to_os_string());
-        for i in 0..10 {
-            let mut path = temp_path.clone();
-            path.push(&format!("/discord-ipc-{}", i));
-            if let Ok(stream) = UnixStream::connect(&path) {
-                let _ = stream.set_nonblocking(true);
-                return Ok(BaseConnection { stream });
+        for location in &config.search_order {
+            for i in config.pipe_indices.clone() {
+                let mut path = temp_path.clone();
+                path.push(format!("{}/discord-ipc-{}", location.subdirectory(), i));
+                if let Ok(mut connection) = Self::connect(&path, config) {
+                    connection.index = Some(i);
+                    return Ok(connection);
+                }
             }
         }
         Err(io::Error::new(
@@ -39,7 +59,7 @@ impl Read for BaseConnection {
 
 impl Write for BaseConnection {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stream.write(buf)
+        write_with_timeout(self.write_timeout, || self.stream.write(buf))
     }
 
     fn flush(&mut self) -> io::Result<()> {
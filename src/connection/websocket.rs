@@ -0,0 +1,510 @@
+use super::{opcode, write_with_timeout, IoTimeouts, IpcConfig, Transport};
+use rand::random;
+use serde_json::json;
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    time::Duration,
+};
+
+pub const BASE_PORT: u16 = 6463;
+
+const UPGRADE_TIMEOUT: Duration = Duration::from_secs(2);
+
+mod ws_opcode {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xA;
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+struct WsFrame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+fn frame_too_long(len: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("WebSocket frame length {} exceeds the maximum", len),
+    )
+}
+
+/// Parses one complete WebSocket frame from the start of `input`, returning it along with its
+/// length in bytes.
+///
+/// Frames longer than `max_len` are rejected as soon as their header arrives, instead of
+/// buffering their payload first.
+fn parse_ws_frame(input: &[u8], max_len: usize) -> io::Result<Option<(WsFrame, usize)>> {
+    if input.len() < 2 {
+        return Ok(None);
+    }
+    let fin = input[0] & 0x80 != 0;
+    let opcode = input[0] & 0x0F;
+    let masked = input[1] & 0x80 != 0;
+    let (len, mut offset) = match input[1] & 0x7F {
+        126 => match input.get(2..4) {
+            Some(len) => (u16::from_be_bytes([len[0], len[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match input.get(2..10) {
+            Some(len) => (u64::from_be_bytes(len.try_into().unwrap()), 10),
+            None => return Ok(None),
+        },
+        len => (len as u64, 2),
+    };
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= max_len)
+        .ok_or_else(|| frame_too_long(len))?;
+    let mask = if masked {
+        match input.get(offset..offset + 4) {
+            Some(mask) => {
+                offset += 4;
+                Some([mask[0], mask[1], mask[2], mask[3]])
+            }
+            None => return Ok(None),
+        }
+    } else {
+        None
+    };
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| frame_too_long(len as u64))?;
+    let mut payload = match input.get(offset..end) {
+        Some(payload) => payload.to_vec(),
+        None => return Ok(None),
+    };
+    if let Some(mask) = mask {
+        for (b, m) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *b ^= m;
+        }
+    }
+    Ok(Some((
+        WsFrame {
+            fin,
+            opcode,
+            payload,
+        },
+        end,
+    )))
+}
+
+/// Connects to Discord's RPC WebSocket server on `127.0.0.1`, trying port `6463 + N` for each
+/// index in [`IpcConfig::pipe_indices`].
+///
+/// The socket is only opened once the handshake frame reveals the client ID, which Discord
+/// expects in the URL; from then on IPC frames are translated to and from WebSocket messages.
+#[derive(Clone, Debug)]
+pub struct WebSocketConnector {
+    /// The `Origin` header to send, which Discord checks against the application's allowed
+    /// RPC origins.
+    pub origin: String,
+}
+
+impl Default for WebSocketConnector {
+    fn default() -> Self {
+        WebSocketConnector {
+            origin: "https://localhost".to_string(),
+        }
+    }
+}
+
+impl super::Connector for WebSocketConnector {
+    type Transport = WebSocketTransport;
+
+    fn connect(&mut self, config: &IpcConfig) -> io::Result<WebSocketTransport> {
+        let ports = config
+            .pipe_indices
+            .clone()
+            .filter_map(|i| u16::try_from(i).ok()?.checked_add(BASE_PORT))
+            .collect::<Vec<_>>();
+        // Fail early when nothing is listening, so fallbacks can kick in before the handshake
+        if !ports.iter().any(|&port| {
+            TcpStream::connect_timeout(
+                &SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+                config.timeouts.connect,
+            )
+            .is_ok()
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not find a Discord RPC WebSocket server",
+            ));
+        }
+        Ok(WebSocketTransport {
+            origin: self.origin.clone(),
+            timeouts: config.timeouts,
+            max_frame_size: config.max_frame_size,
+            ports,
+            stream: None,
+            closed: false,
+            ipc_input: Vec::new(),
+            ws_input: Vec::new(),
+            fragments: Vec::new(),
+            ipc_output: VecDeque::new(),
+        })
+    }
+}
+
+pub struct WebSocketTransport {
+    origin: String,
+    timeouts: IoTimeouts,
+    max_frame_size: usize,
+    ports: Vec<u16>,
+    stream: Option<TcpStream>,
+    closed: bool,
+    ipc_input: Vec<u8>,
+    ws_input: Vec<u8>,
+    fragments: Vec<u8>,
+    ipc_output: VecDeque<u8>,
+}
+
+impl WebSocketTransport {
+    fn upgrade(&self, port: u16, client_id: &str) -> io::Result<(TcpStream, Vec<u8>)> {
+        let mut stream = TcpStream::connect_timeout(
+            &SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            self.timeouts.connect,
+        )?;
+        stream.set_read_timeout(Some(UPGRADE_TIMEOUT))?;
+        let key = base64(&random::<[u8; 16]>());
+        write!(
+            stream,
+            "GET /?v=1&client_id={}&encoding=json HTTP/1.1\r\n\
+             Host: 127.0.0.1:{}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             Origin: {}\r\n\r\n",
+            client_id, port, key, self.origin
+        )?;
+
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        let header_end = loop {
+            let len = stream.read(&mut buf)?;
+            if len == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            response.extend_from_slice(&buf[..len]);
+            if let Some(i) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+                break i + 4;
+            }
+        };
+        if !response.starts_with(b"HTTP/1.1 101") {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                String::from_utf8_lossy(&response[..header_end])
+                    .trim()
+                    .to_string(),
+            ));
+        }
+        stream.set_read_timeout(None)?;
+        stream.set_nonblocking(true)?;
+        Ok((stream, response.split_off(header_end)))
+    }
+
+    fn open(&mut self, client_id: &str) -> io::Result<()> {
+        let mut last_err = io::Error::from(io::ErrorKind::NotFound);
+        for &port in &self.ports {
+            match self.upgrade(port, client_id) {
+                Ok((stream, leftover)) => {
+                    self.stream = Some(stream);
+                    self.ws_input = leftover;
+                    return Ok(());
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn send_ws(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len @ 126..=0xFFFF => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(0x80 | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mask = random::<[u8; 4]>();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        let mut frame = &frame[..];
+        while !frame.is_empty() {
+            match write_with_timeout(self.timeouts.write, || stream.write(frame))? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                len => frame = &frame[len..],
+            }
+        }
+        Ok(())
+    }
+
+    fn queue_ipc(&mut self, opcode: u32, payload: &[u8]) {
+        self.ipc_output.extend(opcode.to_le_bytes());
+        self.ipc_output.extend((payload.len() as u32).to_le_bytes());
+        self.ipc_output.extend(payload);
+    }
+
+    fn receive_ipc(&mut self, opcode: u32, payload: &[u8]) -> io::Result<()> {
+        match opcode {
+            opcode::HANDSHAKE => {
+                let handshake = serde_json::from_slice::<serde_json::Value>(payload)?;
+                let client_id = handshake["client_id"]
+                    .as_str()
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?
+                    .to_string();
+                self.open(&client_id)
+            }
+            opcode::FRAME => self.send_ws(ws_opcode::TEXT, payload),
+            opcode::CLOSE => {
+                self.closed = true;
+                self.send_ws(ws_opcode::CLOSE, &1000_u16.to_be_bytes())
+            }
+            opcode::PING => self.send_ws(ws_opcode::PING, payload),
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_ws_frames(&mut self) -> io::Result<()> {
+        while let Some((frame, len)) = parse_ws_frame(&self.ws_input, self.max_frame_size)? {
+            self.ws_input.drain(..len);
+            match frame.opcode {
+                ws_opcode::TEXT | ws_opcode::BINARY | ws_opcode::CONTINUATION => {
+                    if self.fragments.len() + frame.payload.len() > self.max_frame_size {
+                        return Err(frame_too_long(
+                            (self.fragments.len() + frame.payload.len()) as u64,
+                        ));
+                    }
+                    self.fragments.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        let message = std::mem::take(&mut self.fragments);
+                        self.queue_ipc(opcode::FRAME, &message);
+                    }
+                }
+                ws_opcode::PING => self.send_ws(ws_opcode::PONG, &frame.payload)?,
+                ws_opcode::PONG => self.queue_ipc(opcode::PONG, &frame.payload),
+                ws_opcode::CLOSE => {
+                    let payload = frame.payload;
+                    let code = payload
+                        .get(0..2)
+                        .map_or(1005, |code| u16::from_be_bytes([code[0], code[1]]));
+                    let message = String::from_utf8_lossy(payload.get(2..).unwrap_or_default());
+                    let close = json!({ "code": code, "message": message }).to_string();
+                    self.queue_ipc(opcode::CLOSE, close.as_bytes());
+                    self.closed = true;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        loop {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => return Ok(()),
+            };
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(len) => self.ws_input.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+            // Consume frames as they complete, so the input never holds more than one of them
+            self.handle_ws_frames()?;
+        }
+        self.handle_ws_frames()
+    }
+}
+
+impl Read for WebSocketTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.ipc_output.is_empty() {
+            self.poll()?;
+        }
+        if self.ipc_output.is_empty() {
+            return if self.closed {
+                Ok(0)
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            };
+        }
+        let len = buf.len().min(self.ipc_output.len());
+        for (dst, src) in buf.iter_mut().zip(self.ipc_output.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for WebSocketTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        // Connection writes each IPC frame in pieces, so wait until one is complete
+        self.ipc_input.extend_from_slice(buf);
+        while self.ipc_input.len() >= 8 {
+            let opcode = u32::from_le_bytes(self.ipc_input[0..4].try_into().unwrap());
+            let len = u32::from_le_bytes(self.ipc_input[4..8].try_into().unwrap()) as usize;
+            if self.ipc_input.len() < 8 + len {
+                break;
+            }
+            let frame = self.ipc_input.drain(..8 + len).collect::<Vec<_>>();
+            self.receive_ipc(opcode, &frame[8..])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stream {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Transport for WebSocketTransport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(header: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut frame = header.to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        for (input, output) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(input.as_bytes()), output);
+        }
+        assert_eq!(base64(&[0xFB, 0xFF, 0xBF]), "+/+/");
+    }
+
+    #[test]
+    fn parses_unmasked_frames() {
+        let input = encode(&[0x81, 5], b"Hello");
+        let (frame, len) = parse_ws_frame(&input, 1024).unwrap().unwrap();
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, ws_opcode::TEXT);
+        assert_eq!(frame.payload, b"Hello");
+        assert_eq!(len, input.len());
+    }
+
+    #[test]
+    fn unmasks_frames() {
+        let input = encode(
+            &[0x01, 0x85, 0x37, 0xFA, 0x21, 0x3D],
+            &[0x7F, 0x9F, 0x4D, 0x51, 0x58],
+        );
+        let (frame, len) = parse_ws_frame(&input, 1024).unwrap().unwrap();
+        assert!(!frame.fin);
+        assert_eq!(frame.opcode, ws_opcode::TEXT);
+        assert_eq!(frame.payload, b"Hello");
+        assert_eq!(len, 11);
+    }
+
+    #[test]
+    fn parses_extended_lengths() {
+        let payload = vec![b'a'; 300];
+        let input = encode(&[0x82, 126, 0x01, 0x2C], &payload);
+        let (frame, len) = parse_ws_frame(&input, 1024).unwrap().unwrap();
+        assert_eq!(frame.payload, payload);
+        assert_eq!(len, 304);
+
+        let input = encode(&[0x82, 127, 0, 0, 0, 0, 0, 0, 0x01, 0x2C], &payload);
+        let (frame, len) = parse_ws_frame(&input, 1024).unwrap().unwrap();
+        assert_eq!(frame.payload, payload);
+        assert_eq!(len, 310);
+    }
+
+    #[test]
+    fn leaves_trailing_frames() {
+        let mut input = encode(&[0x89, 0], &[]);
+        input.extend_from_slice(&[0x81, 2, b'h', b'i']);
+        let (frame, len) = parse_ws_frame(&input, 1024).unwrap().unwrap();
+        assert_eq!(frame.opcode, ws_opcode::PING);
+        assert_eq!(len, 2);
+        let (frame, _) = parse_ws_frame(&input[len..], 1024).unwrap().unwrap();
+        assert_eq!(frame.payload, b"hi");
+    }
+
+    #[test]
+    fn waits_for_incomplete_frames() {
+        let input = encode(&[0x81, 0x85, 0x37, 0xFA, 0x21, 0x3D], b"Hello");
+        for len in 0..input.len() {
+            assert!(parse_ws_frame(&input[..len], 1024).unwrap().is_none());
+        }
+        assert!(parse_ws_frame(&[0x81, 126, 0x01], 1024).unwrap().is_none());
+        assert!(parse_ws_frame(&[0x81, 127, 0, 0, 0], 1024)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let err = parse_ws_frame(&[0x81, 126, 0x04, 0x01], 1024)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Lengths that would overflow the end offset are rejected before the payload arrives
+        let err = parse_ws_frame(
+            &[0x81, 127, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            usize::MAX,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
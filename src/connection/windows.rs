@@ -1,23 +1,56 @@
+use super::{write_with_timeout, IpcConfig};
 use std::{
     fs,
     io::{self, Read, Write},
-    path::PathBuf,
+    os::windows::io::AsRawHandle,
+    path::{Path, PathBuf},
+    ptr,
+    time::Duration,
 };
+use winapi::{
+    shared::minwindef::DWORD,
+    um::namedpipeapi::{PeekNamedPipe, SetNamedPipeHandleState},
+};
+
+const PIPE_READMODE_BYTE: DWORD = 0;
+const PIPE_NOWAIT: DWORD = 1;
 
 pub struct BaseConnection {
     file: fs::File,
+    write_timeout: Duration,
+    pub index: Option<u32>,
 }
 
 impl BaseConnection {
-    pub fn open() -> io::Result<Self> {
-        for i in 0..10 {
+    fn connect(path: impl AsRef<Path>, config: &IpcConfig) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().read(true).append(true).open(path)?;
+        // Makes writes to a full pipe return without writing anything instead of blocking, so they
+        // can time out; if this fails, writes just block as before
+        let mut mode = PIPE_READMODE_BYTE | PIPE_NOWAIT;
+        unsafe {
+            SetNamedPipeHandleState(
+                file.as_raw_handle() as *mut _,
+                &mut mode,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+        }
+        Ok(BaseConnection {
+            file,
+            write_timeout: config.timeouts.write,
+            index: None,
+        })
+    }
+
+    pub fn open(config: &IpcConfig) -> io::Result<Self> {
+        if let Some(path) = config.explicit_path() {
+            return Self::connect(path, config);
+        }
+        for i in config.pipe_indices.clone() {
             let path = PathBuf::from(format!(r"\\?\pipe\discord-ipc-{}", i));
-            if let Ok(file) = std::fs::OpenOptions::new()
-                .read(true)
-                .append(true)
-                .open(&path)
-            {
-                return Ok(BaseConnection { file });
+            if let Ok(mut connection) = Self::connect(&path, config) {
+                connection.index = Some(i);
+                return Ok(connection);
             }
         }
         Err(io::Error::new(
@@ -27,15 +60,48 @@ impl BaseConnection {
     }
 }
 
+impl BaseConnection {
+    fn bytes_available(&self) -> io::Result<usize> {
+        let mut available: DWORD = 0;
+        let success = unsafe {
+            PeekNamedPipe(
+                self.file.as_raw_handle() as *mut _,
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                &mut available,
+                ptr::null_mut(),
+            )
+        };
+        if success == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(available as usize)
+    }
+}
+
 impl Read for BaseConnection {
+    // Named pipes opened as files always block, so peek first to match the non-blocking Unix
+    // socket and keep the IO thread from stalling when no frame is pending
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.file.read(buf)
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let available = self.bytes_available()?;
+        if available == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let len = buf.len().min(available);
+        self.file.read(&mut buf[..len])
     }
 }
 
 impl Write for BaseConnection {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.file.write(buf)
+        write_with_timeout(self.write_timeout, || match self.file.write(buf) {
+            Ok(0) if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+            result => result,
+        })
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -0,0 +1,49 @@
+use super::{Event, Snowflake};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    GameJoined(String),
+    StartedSpectating(String),
+    JoinRequested(Snowflake),
+}
+
+impl Key {
+    fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::GameJoined(secret) => Some(Key::GameJoined(secret.clone())),
+            Event::StartedSpectating(secret) => Some(Key::StartedSpectating(secret.clone())),
+            Event::JoinRequested(user) => Some(Key::JoinRequested(user.id)),
+            _ => None,
+        }
+    }
+}
+
+pub struct EventDedup {
+    pub window: Duration,
+    seen: HashMap<Key, Instant>,
+}
+
+impl EventDedup {
+    pub fn new(window: Duration) -> Self {
+        EventDedup {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn is_duplicate(&mut self, event: &Event) -> bool {
+        let key = match Key::of(event) {
+            Some(key) => key,
+            None => return false,
+        };
+        let now = Instant::now();
+        let window = self.window;
+        self.seen
+            .retain(|_, seen_at| now.duration_since(*seen_at) < window);
+        self.seen.insert(key, now).is_some()
+    }
+}
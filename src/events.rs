@@ -0,0 +1,159 @@
+use super::{
+    Channel, DisconnectReason, Error, Guild, GuildStatus, Notification, Relationship, RpcError,
+    Secrets, ShortcutKey, Snowflake, User, VoiceSettings, VoiceState,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize)]
+pub enum Event {
+    Connected(Option<User>),
+    Disconnected(DisconnectReason),
+    GotError(Error),
+    GotRpcError(RpcError),
+    GameJoined(String),
+    StartedSpectating(String),
+    JoinRequested(User),
+    IoThreadRestarted,
+    SecretsRotated(Secrets),
+    /// The keys held down so far while capturing a shortcut.
+    ShortcutChanged(Vec<ShortcutKey>),
+    SubscriptionUpdated(SubscriptionUpdate),
+    RelationshipUpdated(Relationship),
+    VoiceChannelSelected(VoiceChannelSelect),
+    VoiceSettingsUpdated(VoiceSettings),
+    GuildCreated(Guild),
+    GuildStatusUpdated(GuildStatus),
+    ChannelCreated(Channel),
+    OverlayUpdated(OverlayState),
+    VoiceStateCreated(VoiceState),
+    VoiceStateUpdated(VoiceState),
+    VoiceStateDeleted(VoiceState),
+    SpeakingStarted(Speaking),
+    SpeakingStopped(Speaking),
+    NotificationCreated(Notification),
+    InviteReceived(ActivityInvite),
+    /// Any dispatched event, including those also delivered as one of the typed variants; only
+    /// sent while a `raw` handler is set.
+    Raw(String, serde_json::Value),
+}
+
+/// How to answer a user asking to join the current activity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinReply {
+    Accept,
+    Decline,
+    /// Leaves the request unanswered on Discord's side, so it's only dismissed once it expires.
+    Ignore,
+}
+
+/// What a join request policy decides to do with a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinDecision {
+    Accept,
+    Decline,
+    Ignore,
+    /// Passes the request on to the `join_request` handler.
+    Ask,
+}
+
+impl JoinDecision {
+    pub fn reply(self) -> Option<JoinReply> {
+        match self {
+            JoinDecision::Accept => Some(JoinReply::Accept),
+            JoinDecision::Decline => Some(JoinReply::Decline),
+            JoinDecision::Ignore => Some(JoinReply::Ignore),
+            JoinDecision::Ask => None,
+        }
+    }
+}
+
+/// An event subscription, along with the channel it's scoped to for per-channel events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Subscription {
+    pub event: &'static str,
+    pub channel_id: Option<Snowflake>,
+    pub guild_id: Option<Snowflake>,
+}
+
+impl Subscription {
+    pub fn new(event: &'static str) -> Self {
+        Subscription {
+            event,
+            channel_id: None,
+            guild_id: None,
+        }
+    }
+
+    pub fn for_channel(event: &'static str, channel_id: Snowflake) -> Self {
+        Subscription {
+            event,
+            channel_id: Some(channel_id),
+            guild_id: None,
+        }
+    }
+
+    pub fn for_guild(event: &'static str, guild_id: Snowflake) -> Self {
+        Subscription {
+            event,
+            channel_id: None,
+            guild_id: Some(guild_id),
+        }
+    }
+}
+
+/// Discord's answer to a `SUBSCRIBE` or `UNSUBSCRIBE` command.
+#[derive(Clone, Debug, Serialize)]
+pub struct SubscriptionUpdate {
+    pub subscription: Subscription,
+    /// Whether the command subscribed to the event, rather than unsubscribing from it.
+    pub enabled: bool,
+    /// The error Discord rejected the change with, if it did.
+    pub error: Option<RpcError>,
+}
+
+impl SubscriptionUpdate {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct VoiceChannelSelect {
+    #[serde(default)]
+    pub channel_id: Option<Snowflake>,
+    #[serde(default)]
+    pub guild_id: Option<Snowflake>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InvitedActivity {
+    pub session_id: String,
+    pub name: String,
+    pub state: Option<String>,
+    pub details: Option<String>,
+}
+
+/// An invite to join another user's game, sent as a chat message.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ActivityInvite {
+    pub user: User,
+    #[serde(default)]
+    pub activity: InvitedActivity,
+    pub channel_id: Snowflake,
+    pub message_id: Snowflake,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OverlayState {
+    pub enabled: bool,
+    pub locked: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Speaking {
+    pub user_id: Snowflake,
+    #[serde(default)]
+    pub channel_id: Option<Snowflake>,
+}
@@ -0,0 +1,311 @@
+//! A C API mirroring the official `discord_rpc.h`, so games written against Discord's original
+//! library can link against this crate instead, e.g. after building it with
+//! `cargo rustc --lib --features ffi --crate-type cdylib`.
+//!
+//! As with the original, callbacks only run inside `Discord_RunCallbacks`, on the calling thread.
+
+use super::{
+    DisconnectReason, Event, EventHandlers, Images, JoinReply, Party, PartyPrivacy, Presence, Rpc,
+    Secrets, Snowflake, Timestamp, Timestamps, User,
+};
+use parking_lot::{const_mutex, Mutex};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    ptr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+pub const DISCORD_REPLY_NO: c_int = 0;
+pub const DISCORD_REPLY_YES: c_int = 1;
+pub const DISCORD_REPLY_IGNORE: c_int = 2;
+
+pub const DISCORD_PARTY_PRIVATE: c_int = 0;
+pub const DISCORD_PARTY_PUBLIC: c_int = 1;
+
+#[repr(C)]
+pub struct DiscordRichPresence {
+    pub state: *const c_char,
+    pub details: *const c_char,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub large_image_key: *const c_char,
+    pub large_image_text: *const c_char,
+    pub small_image_key: *const c_char,
+    pub small_image_text: *const c_char,
+    pub party_id: *const c_char,
+    pub party_size: c_int,
+    pub party_max: c_int,
+    pub party_privacy: c_int,
+    pub match_secret: *const c_char,
+    pub join_secret: *const c_char,
+    pub spectate_secret: *const c_char,
+    pub instance: i8,
+}
+
+#[repr(C)]
+pub struct DiscordUser {
+    pub user_id: *const c_char,
+    pub username: *const c_char,
+    pub discriminator: *const c_char,
+    pub avatar: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DiscordEventHandlers {
+    pub ready: Option<unsafe extern "C" fn(*const DiscordUser)>,
+    pub disconnected: Option<unsafe extern "C" fn(c_int, *const c_char)>,
+    pub errored: Option<unsafe extern "C" fn(c_int, *const c_char)>,
+    pub join_game: Option<unsafe extern "C" fn(*const c_char)>,
+    pub spectate_game: Option<unsafe extern "C" fn(*const c_char)>,
+    pub join_request: Option<unsafe extern "C" fn(*const DiscordUser)>,
+}
+
+impl DiscordEventHandlers {
+    /// Placeholder handlers that only make the client subscribe to the events C handlers are set
+    /// for, as events are taken out with `drain_events` and passed on separately.
+    fn subscriptions(&self) -> EventHandlers {
+        EventHandlers {
+            join_game: self.join_game.map(|_| Box::new(|_| {}) as _),
+            spectate_game: self.spectate_game.map(|_| Box::new(|_| {}) as _),
+            join_request: self.join_request.map(|_| Box::new(|_| {}) as _),
+            ..Default::default()
+        }
+    }
+}
+
+struct State {
+    rpc: Rpc,
+    handlers: DiscordEventHandlers,
+}
+
+static STATE: Mutex<Option<State>> = const_mutex(None);
+
+unsafe fn read_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let str = CStr::from_ptr(ptr).to_string_lossy();
+    (!str.is_empty()).then(|| str.into_owned())
+}
+
+fn c_string(str: &str) -> CString {
+    CString::new(str).unwrap_or_default()
+}
+
+fn read_timestamp(timestamp: i64) -> Option<Timestamp> {
+    (timestamp > 0).then(|| Timestamp::System(UNIX_EPOCH + Duration::from_secs(timestamp as u64)))
+}
+
+unsafe fn read_presence(presence: &DiscordRichPresence) -> Presence {
+    let timestamps = Timestamps {
+        start: read_timestamp(presence.start_timestamp),
+        end: read_timestamp(presence.end_timestamp),
+        ..Default::default()
+    };
+    let images = Images {
+        large_key: read_str(presence.large_image_key),
+        large_text: read_str(presence.large_image_text),
+        small_key: read_str(presence.small_image_key),
+        small_text: read_str(presence.small_image_text),
+    };
+    let party_id = read_str(presence.party_id);
+    let party = (party_id.is_some() || presence.party_max > 0).then(|| Party {
+        id: party_id,
+        size: (presence.party_max > 0).then_some(presence.party_size.max(0) as u32),
+        max: (presence.party_max > 0).then_some(presence.party_max as u32),
+        privacy: (presence.party_privacy == DISCORD_PARTY_PUBLIC).then_some(PartyPrivacy::Public),
+    });
+    let secrets = Secrets {
+        match_: read_str(presence.match_secret),
+        join: read_str(presence.join_secret),
+        spectate: read_str(presence.spectate_secret),
+    };
+    Presence {
+        state: read_str(presence.state),
+        details: read_str(presence.details),
+        timestamps: (timestamps != Timestamps::default()).then_some(timestamps),
+        images: (images != Images::default()).then_some(images),
+        party,
+        secrets: (secrets != Secrets::default()).then_some(secrets),
+        instance: presence.instance != 0,
+        ..Default::default()
+    }
+}
+
+/// Calls `f` with a C view of `user`, which is only valid for the duration of the call.
+fn with_user(user: Option<&User>, f: impl FnOnce(*const DiscordUser)) {
+    let strings = match user {
+        Some(user) => [
+            c_string(&user.id.to_string()),
+            c_string(&user.username),
+            c_string(&user.discriminator),
+            c_string(user.avatar.as_deref().unwrap_or_default()),
+        ],
+        None => Default::default(),
+    };
+    let user = DiscordUser {
+        user_id: strings[0].as_ptr(),
+        username: strings[1].as_ptr(),
+        discriminator: strings[2].as_ptr(),
+        avatar: strings[3].as_ptr(),
+    };
+    f(&user);
+}
+
+unsafe fn dispatch(handlers: &DiscordEventHandlers, event: Event) {
+    match event {
+        Event::Connected(user) => {
+            if let Some(ready) = handlers.ready {
+                with_user(user.as_ref(), |user| ready(user));
+            }
+        }
+        Event::Disconnected(reason) => {
+            if let Some(disconnected) = handlers.disconnected {
+                let (code, message) = match &reason {
+                    DisconnectReason::Shutdown => (0, String::new()),
+                    _ => reason.error().map_or((0, String::new()), |err| {
+                        (err.code.code() as c_int, err.message.clone())
+                    }),
+                };
+                disconnected(code, c_string(&message).as_ptr());
+            }
+        }
+        Event::GotError(err) => {
+            if let Some(errored) = handlers.errored {
+                errored(err.code.code() as c_int, c_string(&err.message).as_ptr());
+            }
+        }
+        Event::GotRpcError(err) => {
+            if let Some(errored) = handlers.errored {
+                errored(err.code as c_int, c_string(&err.message).as_ptr());
+            }
+        }
+        Event::GameJoined(secret) => {
+            if let Some(join_game) = handlers.join_game {
+                join_game(c_string(&secret).as_ptr());
+            }
+        }
+        Event::StartedSpectating(secret) => {
+            if let Some(spectate_game) = handlers.spectate_game {
+                spectate_game(c_string(&secret).as_ptr());
+            }
+        }
+        Event::JoinRequested(user) => {
+            if let Some(join_request) = handlers.join_request {
+                with_user(Some(&user), |user| join_request(user));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// # Safety
+///
+/// `application_id` must be a valid C string, `handlers` null or a valid pointer, and
+/// `optional_steam_id` null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn Discord_Initialize(
+    application_id: *const c_char,
+    handlers: *const DiscordEventHandlers,
+    auto_register: c_int,
+    optional_steam_id: *const c_char,
+) {
+    let mut state = STATE.lock();
+    if state.is_some() {
+        return;
+    }
+    let app_id = match read_str(application_id) {
+        Some(app_id) => app_id,
+        None => return,
+    };
+    let handlers = handlers.as_ref().copied().unwrap_or_default();
+    let auto_register = auto_register != 0;
+    // An empty Steam ID counts as none, so the game is registered one way or the other
+    let steam_id = read_str(optional_steam_id);
+    #[cfg(any(target_os = "macos", target_family = "windows", target_os = "linux"))]
+    if auto_register {
+        if let Some(steam_id) = &steam_id {
+            let _ = Rpc::register_steam_game(&app_id, steam_id);
+        }
+    }
+    let auto_register = auto_register && steam_id.is_none();
+    *state = Some(State {
+        rpc: Rpc::new(app_id, handlers.subscriptions(), auto_register),
+        handlers,
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn Discord_Shutdown() {
+    // Take the client out first, as dropping it waits for the IO thread to exit
+    let state = STATE.lock().take();
+    drop(state);
+}
+
+#[no_mangle]
+pub extern "C" fn Discord_RunCallbacks() {
+    let (events, handlers) = {
+        let mut state = STATE.lock();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        (state.rpc.drain_events().collect::<Vec<_>>(), state.handlers)
+    };
+    // Handlers commonly call back into the API, e.g. `Discord_Respond`, so the lock can't be held
+    for event in events {
+        unsafe { dispatch(&handlers, event) };
+    }
+}
+
+/// # Safety
+///
+/// `presence` must be null or point to a valid `DiscordRichPresence` whose strings are null or
+/// valid C strings.
+#[no_mangle]
+pub unsafe extern "C" fn Discord_UpdatePresence(presence: *const DiscordRichPresence) {
+    let presence = presence.as_ref().map(|presence| read_presence(presence));
+    if let Some(state) = STATE.lock().as_mut() {
+        let _ = state.rpc.update_presence(presence.as_ref());
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn Discord_ClearPresence() {
+    unsafe { Discord_UpdatePresence(ptr::null()) };
+}
+
+/// # Safety
+///
+/// `user_id` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn Discord_Respond(user_id: *const c_char, reply: c_int) {
+    let user_id = match read_str(user_id).and_then(|user_id| user_id.parse::<Snowflake>().ok()) {
+        Some(user_id) => user_id,
+        None => return,
+    };
+    let reply = match reply {
+        DISCORD_REPLY_YES => JoinReply::Accept,
+        DISCORD_REPLY_NO => JoinReply::Decline,
+        _ => JoinReply::Ignore,
+    };
+    if let Some(state) = STATE.lock().as_mut() {
+        let _ = state.rpc.reply_to_join_request(user_id, reply);
+    }
+}
+
+/// # Safety
+///
+/// `handlers` must be null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn Discord_UpdateHandlers(handlers: *const DiscordEventHandlers) {
+    let handlers = handlers.as_ref().copied().unwrap_or_default();
+    if let Some(state) = STATE.lock().as_mut() {
+        state.handlers = handlers;
+        state
+            .rpc
+            .modify_handlers(|rust_handlers| *rust_handlers = handlers.subscriptions());
+    }
+}
@@ -0,0 +1,55 @@
+use super::{EventHandlers, JoinReply, Presence, Rpc, RpcHandle, Snowflake};
+use parking_lot::{const_mutex, Mutex};
+
+static RPC: Mutex<Option<Rpc>> = const_mutex(None);
+/// Kept separately so presences and join replies can be sent from handlers, which run while
+/// `RPC` is locked.
+static HANDLE: Mutex<Option<RpcHandle>> = const_mutex(None);
+
+/// Runs `f` on the global client; calling it from a handler deadlocks.
+pub fn with_rpc<T>(f: impl FnOnce(&mut Rpc) -> T) -> Option<T> {
+    RPC.lock().as_mut().map(f)
+}
+
+fn handle() -> Option<RpcHandle> {
+    HANDLE.lock().clone()
+}
+
+pub fn init(app_id: impl Into<String>) {
+    init_with(app_id, EventHandlers::default(), true);
+}
+
+pub fn init_with(app_id: impl Into<String>, handlers: EventHandlers, auto_register: bool) {
+    let rpc = Rpc::new(app_id.into(), handlers, auto_register);
+    *HANDLE.lock() = Some(rpc.handle());
+    // Take the previous client out first, as dropping it waits for its IO thread to exit
+    let prev = RPC.lock().replace(rpc);
+    drop(prev);
+}
+
+pub fn update_presence(presence: Option<&Presence>) {
+    if let Some(handle) = handle() {
+        let _ = handle.update_presence(presence);
+    }
+}
+
+pub fn clear_presence() {
+    update_presence(None);
+}
+
+pub fn reply_to_join_request(user_id: Snowflake, reply: JoinReply) {
+    if let Some(handle) = handle() {
+        let _ = handle.reply_to_join_request(user_id, reply);
+    }
+}
+
+/// Runs the handlers of pending events on the calling thread, which may be any thread.
+pub fn run_callbacks() {
+    with_rpc(Rpc::check_events);
+}
+
+pub fn shutdown() {
+    HANDLE.lock().take();
+    let rpc = RPC.lock().take();
+    drop(rpc);
+}
@@ -0,0 +1,186 @@
+use super::{
+    messages, receipt::PendingResponse, validate_activity_json, validate_presence, validation,
+    Error, ErrorCode, Event, JoinReply, Presence, PresenceError, PresenceUpdate, Receipt,
+    SharedState, Snowflake,
+};
+use crossbeam_channel::Sender;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::sync::{atomic::Ordering, Arc};
+
+/// A cloneable handle to an [`Rpc`](crate::Rpc) for sending updates from other threads, while
+/// the owner keeps dispatching events.
+#[derive(Clone)]
+pub struct RpcHandle {
+    pub(crate) shared_state: Arc<SharedState>,
+    pub(crate) message_tx: Sender<Vec<u8>>,
+    pub(crate) event_tx: Sender<Event>,
+}
+
+impl RpcHandle {
+    pub(crate) fn unpark_io_thread(&self) {
+        if let Some(io_thread) = &*self.shared_state.io_thread.lock() {
+            io_thread.unpark();
+        }
+    }
+
+    pub(crate) fn report_violation<R: DeserializeOwned + Clone>(
+        &self,
+        violation: PresenceError,
+    ) -> Receipt<R> {
+        let err = Error {
+            message: violation.to_string(),
+            code: ErrorCode::ValidationFailed,
+        };
+        let _ = self.event_tx.send(Event::GotError(err.clone()));
+        Receipt::resolved(Err(err))
+    }
+
+    pub(crate) fn expect_response<R: DeserializeOwned + Clone>(&self, nonce: i32) -> Receipt<R> {
+        let (tx, receipt) = Receipt::new();
+        self.shared_state
+            .pending_responses
+            .lock()
+            .insert(nonce, PendingResponse::Command(tx));
+        receipt
+    }
+
+    pub(crate) fn send_message<T: Serialize, R: DeserializeOwned + Clone>(
+        &self,
+        nonce: i32,
+        message: &T,
+    ) -> Receipt<R> {
+        let message = match serde_json::to_vec(message) {
+            Ok(message) => message,
+            Err(err) => {
+                return Receipt::resolved(Err(Error {
+                    message: err.to_string(),
+                    code: ErrorCode::WriteFailed,
+                }))
+            }
+        };
+        if self.shared_state.strict.load(Ordering::Relaxed) {
+            if let Err(violation) = validation::validate_frame(&message) {
+                return self.report_violation(violation);
+            }
+        }
+        let receipt = self.expect_response(nonce);
+        let _ = self.message_tx.send(message);
+        self.unpark_io_thread();
+        receipt
+    }
+
+    /// Whether `presence` matches the last one while unchanged presences are being skipped.
+    pub(crate) fn skips_presence(&self, presence: Option<&Presence>) -> bool {
+        if !self
+            .shared_state
+            .skip_unchanged_presence
+            .load(Ordering::Relaxed)
+        {
+            return false;
+        }
+        let current = self.shared_state.presence.lock();
+        current.nonce.is_some() && current.raw.is_none() && current.presence.as_ref() == presence
+    }
+
+    pub(crate) fn publish_presence(&self, presence: Option<Presence>) -> Receipt {
+        self.publish(PresenceUpdate {
+            presence,
+            raw: None,
+            nonce: Some(self.shared_state.nonce.next()),
+            pid: self.shared_state.pid.load(Ordering::Relaxed),
+            pending: false,
+        })
+    }
+
+    fn publish(&self, update: PresenceUpdate) -> Receipt {
+        if self.shared_state.strict.load(Ordering::Relaxed) {
+            if let Some(Err(violation)) = update.presence.as_ref().map(validate_presence) {
+                return self.report_violation(violation);
+            }
+            // Frames are otherwise only serialized on the IO thread
+            if let Err(violation) =
+                validation::validate_frame(&update.to_frame().unwrap_or_default())
+            {
+                return self.report_violation(violation);
+            }
+        }
+        // Expected before the update is visible to the IO thread, which may send it right away
+        let receipt = self.expect_response(update.nonce.unwrap());
+        {
+            let mut current = self.shared_state.presence.lock();
+            // A presence that was never written is superseded, so its receipt won't get a response
+            if current.pending {
+                if let Some(nonce) = current.nonce {
+                    self.shared_state.pending_responses.lock().remove(&nonce);
+                }
+            }
+            *current = PresenceUpdate {
+                pending: true,
+                ..update
+            };
+        }
+        self.unpark_io_thread();
+        receipt
+    }
+
+    pub fn update_presence(&self, presence: Option<&Presence>) -> Receipt {
+        if self.skips_presence(presence) {
+            return Receipt::resolved(Ok(Default::default()));
+        }
+        if let Some(secret_rotation) = &mut *self.shared_state.secret_rotation.lock() {
+            secret_rotation.reset();
+        }
+        self.publish_presence(presence.cloned())
+    }
+
+    pub fn update_presence_json(&self, activity: Value) -> Receipt {
+        if let Err(violation) = validate_activity_json(&activity) {
+            return self.report_violation(violation);
+        }
+        if self
+            .shared_state
+            .skip_unchanged_presence
+            .load(Ordering::Relaxed)
+        {
+            let current = self.shared_state.presence.lock();
+            if current.nonce.is_some() && current.raw.as_ref() == Some(&activity) {
+                return Receipt::resolved(Ok(Default::default()));
+            }
+        }
+        self.publish(PresenceUpdate {
+            presence: None,
+            raw: Some(activity),
+            nonce: Some(self.shared_state.nonce.next()),
+            pid: self.shared_state.pid.load(Ordering::Relaxed),
+            pending: false,
+        })
+    }
+
+    pub fn reply_to_join_request(&self, user_id: Snowflake, reply: JoinReply) -> Receipt {
+        self.shared_state
+            .pending_join_requests
+            .lock()
+            .remove(&user_id);
+        let accepted = match reply {
+            JoinReply::Accept => true,
+            JoinReply::Decline => false,
+            JoinReply::Ignore => return Receipt::resolved(Ok(Default::default())),
+        };
+        if !self.shared_state.is_connected.load(Ordering::Relaxed) {
+            return Receipt::resolved(Err(Error {
+                message: "Not connected".to_string(),
+                code: ErrorCode::NoResponse,
+            }));
+        }
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(
+            nonce,
+            &messages::JoinRequestReply {
+                user_id,
+                accepted,
+                nonce,
+            },
+        )
+    }
+}
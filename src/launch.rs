@@ -0,0 +1,175 @@
+//! Reading the `discord-<app_id>://` URL the game is launched with after registering it with
+//! `auto_register`. Windows and Linux pass the URL as a command-line argument, while macOS sends
+//! it as an Apple Event, see `on_launch_url`.
+
+use std::env;
+
+/// What Discord launched the game to do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LaunchAction {
+    /// Launched without a secret in the URL, in which case the join or spectate secret arrives
+    /// through the `join_game` or `spectate_game` handler once connected.
+    Launch,
+    Join(String),
+    Spectate(String),
+}
+
+fn percent_decode(str: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(str.len());
+    let mut iter = str.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Parses a `discord-<app_id>://` URL, either bare or of the form `join?secret=<SECRET>` or
+/// `spectate?secret=<SECRET>`.
+pub fn parse_launch_url(url: &str) -> Option<LaunchAction> {
+    let (scheme, rest) = url.split_once("://")?;
+    let app_id = scheme.strip_prefix("discord-")?;
+    if app_id.is_empty() || !app_id.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let secret = || {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("secret="))
+            .and_then(percent_decode)
+            .filter(|secret| !secret.is_empty())
+    };
+    match path.trim_matches('/') {
+        "" => Some(LaunchAction::Launch),
+        "join" => secret().map(LaunchAction::Join),
+        "spectate" => secret().map(LaunchAction::Spectate),
+        _ => None,
+    }
+}
+
+/// Finds the launch URL among command-line arguments, e.g. those of [`env::args`].
+pub fn parse_launch_args(args: &[String]) -> Option<LaunchAction> {
+    args.iter().find_map(|arg| parse_launch_url(arg))
+}
+
+/// Parses the current process' arguments with [`parse_launch_args`].
+pub fn launch_action() -> Option<LaunchAction> {
+    parse_launch_args(&env::args().collect::<Vec<_>>())
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::{parse_launch_url, LaunchAction};
+    use parking_lot::{const_mutex, Mutex};
+    use std::os::raw::{c_long, c_short, c_void};
+
+    /// Only ever handled by pointer.
+    #[repr(C)]
+    struct AEDesc {
+        _private: [u8; 0],
+    }
+
+    type AEEventHandler = extern "C" fn(*const AEDesc, *mut AEDesc, *mut c_void) -> c_short;
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn AEInstallEventHandler(
+            event_class: u32,
+            event_id: u32,
+            handler: AEEventHandler,
+            refcon: *mut c_void,
+            is_sys_handler: u8,
+        ) -> c_short;
+        fn AESizeOfParam(
+            event: *const AEDesc,
+            keyword: u32,
+            type_code: *mut u32,
+            data_size: *mut c_long,
+        ) -> c_short;
+        fn AEGetParamPtr(
+            event: *const AEDesc,
+            keyword: u32,
+            desired_type: u32,
+            actual_type: *mut u32,
+            data: *mut c_void,
+            max_size: c_long,
+            actual_size: *mut c_long,
+        ) -> c_short;
+    }
+
+    const K_INTERNET_EVENT_CLASS: u32 = u32::from_be_bytes(*b"GURL");
+    const K_AE_GET_URL: u32 = u32::from_be_bytes(*b"GURL");
+    const KEY_DIRECT_OBJECT: u32 = u32::from_be_bytes(*b"----");
+    const TYPE_UTF8_TEXT: u32 = u32::from_be_bytes(*b"utf8");
+
+    type Callback = Box<dyn FnMut(LaunchAction) + Send>;
+
+    static CALLBACK: Mutex<Option<Callback>> = const_mutex(None);
+
+    unsafe fn read_url(event: *const AEDesc) -> Option<String> {
+        let (mut type_code, mut size) = (0, 0);
+        if AESizeOfParam(event, KEY_DIRECT_OBJECT, &mut type_code, &mut size) != 0 || size <= 0 {
+            return None;
+        }
+        let mut buffer = vec![0_u8; size as usize];
+        let mut actual_size = 0;
+        if AEGetParamPtr(
+            event,
+            KEY_DIRECT_OBJECT,
+            TYPE_UTF8_TEXT,
+            &mut type_code,
+            buffer.as_mut_ptr().cast(),
+            size,
+            &mut actual_size,
+        ) != 0
+        {
+            return None;
+        }
+        buffer.truncate(actual_size.clamp(0, size) as usize);
+        String::from_utf8(buffer).ok()
+    }
+
+    extern "C" fn handle_get_url(
+        event: *const AEDesc,
+        _reply: *mut AEDesc,
+        _refcon: *mut c_void,
+    ) -> c_short {
+        if let Some(action) = unsafe { read_url(event) }.and_then(|url| parse_launch_url(&url)) {
+            if let Some(callback) = &mut *CALLBACK.lock() {
+                callback(action);
+            }
+        }
+        0
+    }
+
+    /// Calls `callback` with every launch URL macOS sends the game as an Apple Event, including
+    /// the one it was started with as long as this is called before the application finishes
+    /// launching. The events are only delivered while the main thread runs the application's
+    /// event loop.
+    pub fn on_launch_url(
+        callback: impl FnMut(LaunchAction) + Send + 'static,
+    ) -> Result<(), String> {
+        *CALLBACK.lock() = Some(Box::new(callback));
+        let status = unsafe {
+            AEInstallEventHandler(
+                K_INTERNET_EVENT_CLASS,
+                K_AE_GET_URL,
+                handle_get_url,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if status != 0 {
+            return Err(format!("Error in AEInstallEventHandler: {}", status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use macos::on_launch_url;
@@ -1,81 +1,385 @@
 mod backoff;
+pub use backoff::{Jitter, ReconnectPolicy};
+mod builder;
+pub use builder::PresenceBuilder;
+mod capabilities;
+mod client;
+pub use capabilities::{Capabilities, Capability};
+pub use client::RpcClient;
 mod connection;
-pub use connection::StreamError as Error;
+mod dedup;
+mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "global")]
+mod global;
+mod handle;
+#[cfg(feature = "global")]
+pub use global::*;
+pub use handle::RpcHandle;
+mod macros;
+pub use connection::{
+    BaseConnection, Connector, DisconnectReason, ErrorCode, IoTimeouts, IpcConfig, IpcConnector,
+    IpcLocation, JsonReadError, JsonWriteError, Keepalive, OpenError, RawWriteError,
+    StreamError as Error, TrafficStats, Transport, IPC_PATH_VAR,
+};
+pub use connection::{FallbackConnector, FallbackTransport};
+#[cfg(feature = "websocket")]
+pub use connection::{WebSocketConnector, WebSocketTransport};
+pub use events::*;
+pub mod launch;
 mod messages;
+pub use messages::{Application, Authentication, Authorization, Notification, ServerConfig};
+mod models;
+pub use models::*;
 mod presence;
+mod rate_limit;
+pub use rate_limit::RateLimit;
+mod receipt;
+pub use receipt::{CommandHandle, CommandResult, Receipt};
 mod register;
+mod rotation;
+mod rpc_error;
+pub use rpc_error::{RpcError, RpcErrorCode};
+mod session;
+mod validation;
+pub use validation::{validate_activity_json, validate_presence, PresenceError};
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub use session::Session;
+mod stats;
+pub use stats::{ConnectionStatus, Stats};
+mod snowflake;
+pub use snowflake::Snowflake;
+mod voice;
+#[cfg(feature = "watcher")]
+mod watcher;
+#[cfg(feature = "derive")]
+pub use discord_rpc_derive::ToPresence;
 pub use presence::*;
+pub use voice::*;
 
 use backoff::Backoff;
 use connection::Connection;
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use dedup::EventDedup;
 use parking_lot::Mutex;
-use serde::Serialize;
+use rate_limit::RateLimiter;
+use receipt::PendingResponse;
+use rotation::SecretRotation;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    cell::RefCell,
-    process,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    env, iter, mem, process,
     rc::Rc,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering},
         Arc,
     },
-    thread::{self, JoinHandle},
+    thread::{self, JoinHandle, Thread},
     time::{Duration, Instant},
 };
 
 const MAX_IO_THREAD_TIMEOUT: Duration = Duration::from_millis(500);
 
-#[derive(Clone, Debug)]
-enum Event {
-    Connected(Option<User>),
-    Disconnected(Option<Error>),
-    GotError(Error),
-    GameJoined(String),
-    StartedSpectating(String),
-    JoinRequested(User),
-}
+/// Starts clients disabled when set to anything but `0`, see [`Rpc::set_enabled`].
+pub const DISABLED_VAR: &str = "DISCORD_RPC_DISABLED";
+
+pub type JoinRequestPolicy = Box<dyn FnMut(&User) -> JoinDecision + Send>;
 
 #[derive(Default)]
 pub struct EventHandlers {
-    pub connect: Option<Box<dyn FnMut(Option<User>)>>,
-    pub disconnect: Option<Box<dyn FnMut(Option<Error>)>>,
-    pub error: Option<Box<dyn FnMut(Error)>>,
-    pub join_game: Option<Box<dyn FnMut(String)>>,
-    pub spectate_game: Option<Box<dyn FnMut(String)>>,
-    pub join_request: Option<Box<dyn FnMut(User)>>,
+    pub connect: Option<Box<dyn FnMut(Option<User>) + Send>>,
+    pub disconnect: Option<Box<dyn FnMut(DisconnectReason) + Send>>,
+    pub error: Option<Box<dyn FnMut(Error) + Send>>,
+    /// Receives the errors Discord reports through `ERROR` events, while `error` receives those
+    /// raised locally.
+    pub rpc_error: Option<Box<dyn FnMut(RpcError) + Send>>,
+    pub join_game: Option<Box<dyn FnMut(String) + Send>>,
+    pub spectate_game: Option<Box<dyn FnMut(String) + Send>>,
+    pub join_request: Option<Box<dyn FnMut(User) + Send>>,
+    /// Answers join requests when [`Rpc::check_events`] or [`Rpc::try_recv_event`] takes them off
+    /// the event queue, before they reach `join_request`, so like any handler it only runs as
+    /// often as events are polled. Requests it returns [`JoinDecision::Ask`] for are delivered as
+    /// usual.
+    pub join_request_policy: Option<JoinRequestPolicy>,
+    pub invite: Option<Box<dyn FnMut(ActivityInvite) + Send>>,
+    pub relationship_update: Option<Box<dyn FnMut(Relationship) + Send>>,
+    pub voice_channel_select: Option<Box<dyn FnMut(VoiceChannelSelect) + Send>>,
+    pub voice_settings_update: Option<Box<dyn FnMut(VoiceSettings) + Send>>,
+    pub guild_create: Option<Box<dyn FnMut(Guild) + Send>>,
+    /// The guilds `guild_status` listens to; requires the `rpc` scope.
+    pub status_guilds: Vec<Snowflake>,
+    pub guild_status: Option<Box<dyn FnMut(GuildStatus) + Send>>,
+    pub channel_create: Option<Box<dyn FnMut(Channel) + Send>>,
+    pub overlay_update: Option<Box<dyn FnMut(OverlayState) + Send>>,
+    pub notification_create: Option<Box<dyn FnMut(Notification) + Send>>,
+    pub secrets_rotated: Option<Box<dyn FnMut(Secrets) + Send>>,
+    /// Receives the keys held down while a shortcut is being captured, see
+    /// [`Rpc::start_shortcut_capture`].
+    pub shortcut_change: Option<Box<dyn FnMut(Vec<ShortcutKey>) + Send>>,
+    pub io_thread_restarted: Option<Box<dyn FnMut() + Send>>,
+    /// Receives Discord's answer to every subscription change, so rejected subscriptions (e.g.
+    /// for lack of approval or scope) don't go unnoticed.
+    pub subscription_update: Option<Box<dyn FnMut(SubscriptionUpdate) + Send>>,
+    /// Receives the name and payload of every event Discord dispatches, whether or not this crate
+    /// models it; other events still need their own handler to be subscribed to.
+    pub raw: Option<Box<dyn FnMut(String, serde_json::Value) + Send>>,
+    /// The voice channel the voice state and speaking handlers below listen to; they're only
+    /// subscribed while this is set.
+    pub voice_channel: Option<Snowflake>,
+    pub voice_state_create: Option<Box<dyn FnMut(VoiceState) + Send>>,
+    pub voice_state_update: Option<Box<dyn FnMut(VoiceState) + Send>>,
+    pub voice_state_delete: Option<Box<dyn FnMut(VoiceState) + Send>>,
+    pub speaking_start: Option<Box<dyn FnMut(Speaking) + Send>>,
+    pub speaking_stop: Option<Box<dyn FnMut(Speaking) + Send>>,
+}
+
+impl EventHandlers {
+    fn dispatch(&mut self, event: Event) {
+        macro_rules! run_cb {
+            ($callback: expr, $($args: tt)*) => {
+                if let Some(callback) = &mut $callback {
+                    callback($($args)*);
+                }
+            }
+        }
+        match event {
+            Event::Connected(user) => run_cb!(self.connect, user),
+            Event::Disconnected(reason) => run_cb!(self.disconnect, reason),
+            Event::GotError(err) => run_cb!(self.error, err),
+            Event::GotRpcError(err) => run_cb!(self.rpc_error, err),
+            Event::GameJoined(secret) => run_cb!(self.join_game, secret),
+            Event::StartedSpectating(secret) => run_cb!(self.spectate_game, secret),
+            Event::JoinRequested(user) => run_cb!(self.join_request, user),
+            Event::InviteReceived(invite) => run_cb!(self.invite, invite),
+            Event::Raw(event, data) => run_cb!(self.raw, event, data),
+            Event::IoThreadRestarted => run_cb!(self.io_thread_restarted,),
+            Event::SecretsRotated(secrets) => run_cb!(self.secrets_rotated, secrets),
+            Event::ShortcutChanged(shortcut) => run_cb!(self.shortcut_change, shortcut),
+            Event::SubscriptionUpdated(update) => run_cb!(self.subscription_update, update),
+            Event::RelationshipUpdated(relationship) => {
+                run_cb!(self.relationship_update, relationship)
+            }
+            Event::VoiceChannelSelected(selection) => {
+                run_cb!(self.voice_channel_select, selection)
+            }
+            Event::VoiceSettingsUpdated(settings) => {
+                run_cb!(self.voice_settings_update, settings)
+            }
+            Event::GuildCreated(guild) => run_cb!(self.guild_create, guild),
+            Event::GuildStatusUpdated(status) => run_cb!(self.guild_status, status),
+            Event::ChannelCreated(channel) => run_cb!(self.channel_create, channel),
+            Event::OverlayUpdated(overlay) => run_cb!(self.overlay_update, overlay),
+            Event::VoiceStateCreated(state) => run_cb!(self.voice_state_create, state),
+            Event::VoiceStateUpdated(state) => run_cb!(self.voice_state_update, state),
+            Event::VoiceStateDeleted(state) => run_cb!(self.voice_state_delete, state),
+            Event::SpeakingStarted(speaking) => run_cb!(self.speaking_start, speaking),
+            Event::SpeakingStopped(speaking) => run_cb!(self.speaking_stop, speaking),
+            Event::NotificationCreated(notification) => {
+                run_cb!(self.notification_create, notification)
+            }
+        }
+    }
+
+    fn decide_join_request(&mut self, user: &User) -> Option<JoinReply> {
+        self.join_request_policy.as_mut()?(user).reply()
+    }
+
+    fn subscriptions(&self) -> Vec<Subscription> {
+        let mut subscriptions = Vec::new();
+        macro_rules! subscriptions {
+            ($($handler: ident => $event: expr),*$(,)?) => {
+                $(
+                    if self.$handler.is_some() {
+                        subscriptions.push(Subscription::new($event));
+                    }
+                )*
+            };
+        }
+        subscriptions!(
+            join_game => "ACTIVITY_JOIN",
+            spectate_game => "ACTIVITY_SPECTATE",
+            join_request => "ACTIVITY_JOIN_REQUEST",
+            invite => "ACTIVITY_INVITE",
+            relationship_update => "RELATIONSHIP_UPDATE",
+            voice_channel_select => "VOICE_CHANNEL_SELECT",
+            voice_settings_update => "VOICE_SETTINGS_UPDATE",
+            guild_create => "GUILD_CREATE",
+            channel_create => "CHANNEL_CREATE",
+            overlay_update => "OVERLAY_UPDATE",
+            notification_create => "NOTIFICATION_CREATE",
+            shortcut_change => "CAPTURE_SHORTCUT_CHANGE",
+        );
+        if self.join_request.is_none() && self.join_request_policy.is_some() {
+            subscriptions.push(Subscription::new("ACTIVITY_JOIN_REQUEST"));
+        }
+        if self.guild_status.is_some() {
+            subscriptions.extend(
+                self.status_guilds
+                    .iter()
+                    .map(|&guild_id| Subscription::for_guild("GUILD_STATUS", guild_id)),
+            );
+        }
+        if let Some(channel_id) = self.voice_channel {
+            macro_rules! channel_subscriptions {
+                ($($handler: ident => $event: expr),*$(,)?) => {
+                    $(
+                        if self.$handler.is_some() {
+                            subscriptions.push(Subscription::for_channel($event, channel_id));
+                        }
+                    )*
+                };
+            }
+            channel_subscriptions!(
+                voice_state_create => "VOICE_STATE_CREATE",
+                voice_state_update => "VOICE_STATE_UPDATE",
+                voice_state_delete => "VOICE_STATE_DELETE",
+                speaking_start => "SPEAKING_START",
+                speaking_stop => "SPEAKING_STOP",
+            );
+        }
+        subscriptions
+    }
 }
 
-struct Nonce(i32);
+struct Nonce(AtomicI32);
 
 impl Nonce {
-    fn next(&mut self) -> i32 {
-        let result = self.0;
-        self.0 = self.0.wrapping_add(1);
-        result
+    fn next(&self) -> i32 {
+        self.0.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShutdownOptions {
+    /// Whether to send an empty activity so the presence disappears immediately.
+    pub clear_presence: bool,
+    /// How long to wait for the IO thread to exit before leaving it to finish on its own.
+    pub timeout: Duration,
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        ShutdownOptions {
+            clear_presence: true,
+            timeout: Duration::from_millis(500),
+        }
     }
 }
 
 pub struct Rpc {
     shared_state: Arc<SharedState>,
-    message_tx: Sender<Vec<u8>>,
+    handle: RpcHandle,
+    message_rx: Receiver<Vec<u8>>,
     event_rx: Receiver<Event>,
     io_thread: Option<JoinHandle<()>>,
+    spawn_io_thread: IoThreadSpawner,
+    app_id: String,
 
     handlers: EventHandlers,
+    join_request_timeout: Option<(Duration, JoinReply)>,
+    event_dedup: Option<EventDedup>,
+    clear_presence_on_drop: bool,
+    capturing_shortcut: bool,
+    #[cfg(feature = "watcher")]
+    presence_file: Option<watcher::PresenceFileWatcher>,
+}
+
+/// The latest presence along with the nonce and pid its `SET_ACTIVITY` frame is sent with, the
+/// nonce being `None` until a presence is first published. A `raw` activity is sent in place of
+/// `presence` when set.
+#[derive(Default)]
+struct PresenceUpdate {
+    presence: Option<Presence>,
+    raw: Option<serde_json::Value>,
+    nonce: Option<i32>,
     pid: u32,
-    nonce: Nonce,
+    /// Whether the update still has to be sent; only changed along with the rest of it, so the IO
+    /// thread can't send an update before it's complete, or twice.
+    pending: bool,
+}
+
+impl PresenceUpdate {
+    fn to_frame(&self) -> Option<Vec<u8>> {
+        let (pid, nonce) = (self.pid, self.nonce?);
+        match &self.raw {
+            Some(raw) => serde_json::to_vec(&messages::SetActivity {
+                pid,
+                nonce,
+                activity: Some(raw),
+            }),
+            None => serde_json::to_vec(&messages::SetActivity {
+                pid,
+                nonce,
+                activity: self.presence.as_ref(),
+            }),
+        }
+        .ok()
+    }
 }
 
 struct SharedState {
-    presence: Mutex<Vec<u8>>,
-    presence_updated: AtomicBool,
+    presence: Mutex<PresenceUpdate>,
     is_connected: AtomicBool,
+    enabled: AtomicBool,
     stopped: AtomicBool,
+    raw_events: AtomicBool,
+    current_user: Mutex<Option<User>>,
+    status: Mutex<ConnectionStatus>,
+    reconnect_policy: Mutex<ReconnectPolicy>,
+    keepalive: Mutex<Option<Keepalive>>,
+    server_config: Mutex<Option<ServerConfig>>,
+    nonce: Nonce,
+    subscriptions: Mutex<HashSet<Subscription>>,
+    subscription_changes: Mutex<HashMap<Subscription, bool>>,
+    pending_responses: Mutex<HashMap<i32, PendingResponse>>,
+    capabilities: Mutex<Capabilities>,
+    ipc_config: Mutex<IpcConfig>,
+    presence_rate_limit: Mutex<Option<RateLimit>>,
+    stats: Mutex<Stats>,
+    pending_join_requests: Mutex<HashMap<Snowflake, Instant>>,
+    /// Shared so that presences published through handles restart the rotation timer as well.
+    secret_rotation: Mutex<Option<SecretRotation>>,
+    /// The latest keys reported while capturing a shortcut.
+    captured_shortcut: Mutex<Vec<ShortcutKey>>,
+    /// The application to connect as, which the IO thread reconnects for whenever it changes.
+    app_id: Mutex<String>,
+    pid: AtomicU32,
+    strict: AtomicBool,
+    skip_unchanged_presence: AtomicBool,
+    /// The current IO thread, so handles can wake it up after it's been restarted.
+    io_thread: Mutex<Option<Thread>>,
 }
 
 impl Rpc {
+    /// Creates a client connected through the platform IPC socket, or with the `websocket`
+    /// feature, through Discord's WebSocket server if no IPC socket can be found.
     pub fn new(app_id: String, handlers: EventHandlers, auto_register: bool) -> Self {
-        #[cfg(target_os = "macos")] // TODO: Support other OSes too
+        #[cfg(feature = "websocket")]
+        let connector = FallbackConnector {
+            primary: IpcConnector,
+            fallback: WebSocketConnector::default(),
+        };
+        #[cfg(not(feature = "websocket"))]
+        let connector = IpcConnector;
+        Self::with_connector(app_id, handlers, auto_register, connector)
+    }
+
+    /// Creates a client that reaches Discord through a custom [`Connector`] instead of the
+    /// platform IPC socket.
+    #[cfg_attr(
+        not(any(target_os = "macos", target_family = "windows", target_os = "linux")),
+        allow(unused_variables)
+    )]
+    pub fn with_connector(
+        app_id: String,
+        handlers: EventHandlers,
+        auto_register: bool,
+        connector: impl Connector + Clone,
+    ) -> Self {
+        #[cfg(any(target_os = "macos", target_family = "windows", target_os = "linux"))]
         if auto_register {
             let _ = register::register_url(&app_id);
         }
@@ -84,129 +388,762 @@ impl Rpc {
         let (event_tx, event_rx) = crossbeam_channel::unbounded();
 
         let shared_state = Arc::new(SharedState {
-            presence: Mutex::new(Vec::new()),
-            presence_updated: AtomicBool::new(false),
+            presence: Mutex::new(PresenceUpdate::default()),
             is_connected: AtomicBool::new(false),
+            enabled: AtomicBool::new(
+                env::var_os(DISABLED_VAR)
+                    .is_none_or(|disabled| disabled.is_empty() || disabled == "0"),
+            ),
             stopped: AtomicBool::new(false),
+            raw_events: AtomicBool::new(handlers.raw.is_some()),
+            current_user: Mutex::new(None),
+            status: Mutex::new(ConnectionStatus::Disconnected { last_error: None }),
+            reconnect_policy: Mutex::new(ReconnectPolicy::default()),
+            keepalive: Mutex::new(Some(Keepalive::default())),
+            server_config: Mutex::new(None),
+            nonce: Nonce(AtomicI32::new(1)),
+            subscriptions: Mutex::new(handlers.subscriptions().into_iter().collect()),
+            subscription_changes: Mutex::new(HashMap::new()),
+            pending_responses: Mutex::new(HashMap::new()),
+            capabilities: Mutex::new(Capabilities::default()),
+            ipc_config: Mutex::new(IpcConfig::default()),
+            presence_rate_limit: Mutex::new(Some(RateLimit::default())),
+            stats: Mutex::new(Stats::default()),
+            pending_join_requests: Mutex::new(HashMap::new()),
+            secret_rotation: Mutex::new(None),
+            captured_shortcut: Mutex::new(Vec::new()),
+            app_id: Mutex::new(app_id.clone()),
+            pid: AtomicU32::new(process::id()),
+            strict: AtomicBool::new(false),
+            skip_unchanged_presence: AtomicBool::new(false),
+            io_thread: Mutex::new(None),
         });
 
-        let shared_state_clone = Arc::clone(&shared_state);
-        let io_thread = thread::Builder::new()
-            .name("Discord RPC".to_string())
-            .spawn(move || run_io_thread(app_id, message_rx, event_tx, shared_state_clone))
-            .expect("Couldn't spawn Discord RPC IO thread");
+        let spawn_io_thread: IoThreadSpawner =
+            Box::new(move |app_id, message_rx, event_tx, shared_state| {
+                spawn_io_thread(
+                    connector.clone(),
+                    app_id,
+                    message_rx,
+                    event_tx,
+                    shared_state,
+                )
+            });
+        let io_thread = spawn_io_thread(&app_id, &message_rx, &event_tx, &shared_state);
 
         Rpc {
+            handle: RpcHandle {
+                shared_state: Arc::clone(&shared_state),
+                message_tx,
+                event_tx,
+            },
             shared_state,
-            message_tx,
+            message_rx,
             event_rx,
             io_thread: Some(io_thread),
+            spawn_io_thread,
+            app_id,
 
             handlers,
-            pid: process::id(),
-            nonce: Nonce(1),
+            join_request_timeout: None,
+            event_dedup: None,
+            clear_presence_on_drop: false,
+            capturing_shortcut: false,
+            #[cfg(feature = "watcher")]
+            presence_file: None,
         }
     }
 
-    fn send_message<T: Serialize>(&self, message: &T) -> serde_json::Result<()> {
-        let _ = self.message_tx.send(serde_json::to_vec(message)?);
-        self.io_thread.as_ref().unwrap().thread().unpark();
-        Ok(())
+    /// Registers the `discord-<app_id>` URL protocol to launch the game through Steam instead of
+    /// the current executable; use this in place of `auto_register` for Steam titles.
+    #[cfg(any(target_os = "macos", target_family = "windows", target_os = "linux"))]
+    pub fn register_steam_game(app_id: &str, steam_id: &str) -> Result<(), String> {
+        register::register_steam_game(app_id, steam_id)
     }
 
-    fn toggle_event_subscription<const ENABLED: bool>(&mut self, event: &str) {
-        let nonce = self.nonce.next();
-        let _ = self.send_message(&messages::ToggleSubscription::<ENABLED> { nonce, event });
+    /// Disconnects from Discord while `false`, which clears the activity there and reports a
+    /// [`DisconnectReason::Disabled`] disconnection, and reconnects with the latest presence and
+    /// subscriptions once enabled again. Commands sent in the meantime fail with `NO_RESPONSE`.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.shared_state.enabled.store(enabled, Ordering::Relaxed);
+        self.handle.unpark_io_thread();
     }
 
-    pub fn modify_handlers(&mut self, f: impl FnOnce(&mut EventHandlers)) {
-        let had_join_game_handler = self.handlers.join_game.is_some();
-        let had_spectate_game_handler = self.handlers.spectate_game.is_some();
-        let had_join_request_handler = self.handlers.join_request.is_some();
-        f(&mut self.handlers);
-        macro_rules! toggle_event_subscription {
-            ($prev: expr, $new: expr, $name: expr) => {
-                match ($prev, $new) {
-                    (false, true) => self.toggle_event_subscription::<true>($name),
-                    (true, false) => self.toggle_event_subscription::<false>($name),
-                    _ => {}
+    pub fn is_enabled(&self) -> bool {
+        self.shared_state.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Reconnects as another application, emitting the usual disconnect and connect events and
+    /// subscribing to the same events again. The presence is cleared, as it was set for the
+    /// previous application.
+    pub fn set_app_id(&mut self, app_id: String) {
+        if app_id == self.app_id {
+            return;
+        }
+        {
+            let mut presence = self.shared_state.presence.lock();
+            if presence.pending {
+                if let Some(nonce) = presence.nonce {
+                    self.shared_state.pending_responses.lock().remove(&nonce);
                 }
-            };
+            }
+            presence.presence = None;
+            presence.raw = None;
+            presence.nonce = None;
+            presence.pending = false;
         }
-        toggle_event_subscription!(
-            had_join_game_handler,
-            self.handlers.join_game.is_some(),
-            "ACTIVITY_JOIN"
-        );
-        toggle_event_subscription!(
-            had_spectate_game_handler,
-            self.handlers.spectate_game.is_some(),
-            "ACTIVITY_SPECTATE"
-        );
-        toggle_event_subscription!(
-            had_join_request_handler,
-            self.handlers.join_request.is_some(),
-            "ACTIVITY_JOIN_REQUEST"
-        );
+        self.shared_state.pending_join_requests.lock().clear();
+        self.shared_state.app_id.lock().clone_from(&app_id);
+        self.app_id = app_id;
+        self.handle.unpark_io_thread();
     }
 
-    pub fn update_presence(&mut self, presence: Option<&Presence>) {
-        {
-            let mut presence_raw = self.shared_state.presence.lock();
-            presence_raw.clear();
-            let _ = serde_json::to_writer(
-                &mut *presence_raw,
-                &messages::SetActivity {
-                    pid: self.pid,
-                    nonce: self.nonce.next(),
-                    presence,
-                },
-            );
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    /// Attributes the activity to another process than this one, e.g. the game a launcher
+    /// started; the current presence is sent again for it right away.
+    pub fn set_target_pid(&mut self, pid: u32) {
+        self.shared_state.pid.store(pid, Ordering::Relaxed);
+        self.shared_state.presence.lock().pid = pid;
+        if self.shared_state.is_connected.load(Ordering::Relaxed) {
+            resend_last_presence(&self.shared_state);
         }
+        self.handle.unpark_io_thread();
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.shared_state.strict.store(strict, Ordering::Relaxed);
+    }
+
+    /// Makes presence updates identical to the last one return a resolved receipt without sending
+    /// anything, for callers that update every frame.
+    pub fn set_skip_unchanged_presence(&mut self, skip: bool) {
         self.shared_state
-            .presence_updated
-            .store(true, Ordering::Release);
-        self.io_thread.as_ref().unwrap().thread().unpark();
+            .skip_unchanged_presence
+            .store(skip, Ordering::Relaxed);
     }
 
-    pub fn reply_to_join_request(&mut self, user_id: &str, accepted: bool) {
-        if !self.shared_state.is_connected.load(Ordering::Relaxed) {
+    /// Makes dropping the client send a cleared activity before disconnecting, so it doesn't
+    /// linger in Discord; like [`ShutdownOptions::clear_presence`] without having to call
+    /// [`shutdown`](Self::shutdown).
+    pub fn set_clear_presence_on_drop(&mut self, clear: bool) {
+        self.clear_presence_on_drop = clear;
+    }
+
+    /// Returns a handle that can update the presence and answer join requests from other
+    /// threads.
+    pub fn handle(&self) -> RpcHandle {
+        self.handle.clone()
+    }
+
+    fn send_message<T: Serialize, R: DeserializeOwned + Clone>(
+        &self,
+        nonce: i32,
+        message: &T,
+    ) -> Receipt<R> {
+        self.handle.send_message(nonce, message)
+    }
+
+    fn toggle_event_subscription(&mut self, subscription: Subscription, enabled: bool) {
+        {
+            let mut subscriptions = self.shared_state.subscriptions.lock();
+            if enabled {
+                subscriptions.insert(subscription);
+            } else {
+                subscriptions.remove(&subscription);
+            }
+            let mut subscription_changes = self.shared_state.subscription_changes.lock();
+            if subscription_changes.get(&subscription) == Some(&!enabled) {
+                subscription_changes.remove(&subscription);
+            } else {
+                subscription_changes.insert(subscription, enabled);
+            }
+        }
+        self.handle.unpark_io_thread();
+    }
+
+    /// The handlers' subscriptions, along with those needed by the client itself.
+    fn subscriptions(&self) -> Vec<Subscription> {
+        let mut subscriptions = self.handlers.subscriptions();
+        let shortcut_change = Subscription::new("CAPTURE_SHORTCUT_CHANGE");
+        if self.capturing_shortcut && !subscriptions.contains(&shortcut_change) {
+            subscriptions.push(shortcut_change);
+        }
+        subscriptions
+    }
+
+    pub fn modify_handlers(&mut self, f: impl FnOnce(&mut EventHandlers)) {
+        self.modify_subscriptions(|rpc| {
+            f(&mut rpc.handlers);
+            rpc.shared_state
+                .raw_events
+                .store(rpc.handlers.raw.is_some(), Ordering::Relaxed);
+        });
+    }
+
+    fn modify_subscriptions(&mut self, f: impl FnOnce(&mut Self)) {
+        let prev_subscriptions = self.subscriptions();
+        f(self);
+        let new_subscriptions = self.subscriptions();
+        for &subscription in &new_subscriptions {
+            if !prev_subscriptions.contains(&subscription) {
+                self.toggle_event_subscription(subscription, true);
+            }
+        }
+        for &subscription in &prev_subscriptions {
+            if !new_subscriptions.contains(&subscription) {
+                self.toggle_event_subscription(subscription, false);
+            }
+        }
+    }
+
+    pub fn activate_session(&mut self, session: Session) -> Session {
+        let mut handlers = session.handlers;
+        self.modify_handlers(|current| mem::swap(current, &mut handlers));
+        let presence = self.last_presence();
+        let _ = self.update_presence(session.presence.as_ref());
+        Session { presence, handlers }
+    }
+
+    pub fn deactivate_session(&mut self) -> Session {
+        self.activate_session(Session::default())
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        *self.shared_state.capabilities.lock()
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        self.shared_state.status.lock().clone()
+    }
+
+    /// The user Discord reported on the most recent connection, if any.
+    pub fn current_user(&self) -> Option<User> {
+        self.shared_state.current_user.lock().clone()
+    }
+
+    /// The environment Discord reported on the most recent connection, if any.
+    pub fn server_config(&self) -> Option<ServerConfig> {
+        self.shared_state.server_config.lock().clone()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.shared_state.stats.lock().clone()
+    }
+
+    /// Changes where the IPC socket is searched for; takes effect on the next connection attempt.
+    pub fn set_ipc_config(&mut self, config: IpcConfig) {
+        *self.shared_state.ipc_config.lock() = config;
+    }
+
+    /// Changes how long connecting, writing and the handshake may take, keeping the rest of the
+    /// IPC config; takes effect on the next connection attempt.
+    pub fn set_io_timeouts(&mut self, timeouts: IoTimeouts) {
+        self.shared_state.ipc_config.lock().timeouts = timeouts;
+    }
+
+    /// Changes how reconnection attempts are spaced and when they stop; changing the policy also
+    /// restarts the attempt count, resuming attempts if they had been given up.
+    pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+        *self.shared_state.reconnect_policy.lock() = policy;
+        self.handle.unpark_io_thread();
+    }
+
+    /// Configures the PINGs used to detect an unresponsive Discord client, sent every 30 seconds
+    /// of silence by default; `None` disables them.
+    pub fn set_keepalive(&mut self, keepalive: Option<Keepalive>) {
+        *self.shared_state.keepalive.lock() = keepalive;
+        self.handle.unpark_io_thread();
+    }
+
+    /// Limits how often presence updates are sent, 5 every 20 seconds by default to match
+    /// Discord's throttling; `None` sends every update immediately.
+    pub fn set_presence_rate_limit(&mut self, rate_limit: Option<RateLimit>) {
+        *self.shared_state.presence_rate_limit.lock() = rate_limit;
+        self.handle.unpark_io_thread();
+    }
+
+    pub fn update_presence(&mut self, presence: Option<&Presence>) -> Receipt {
+        self.handle.update_presence(presence)
+    }
+
+    /// Sends an activity given as raw JSON in place of a [`Presence`], for fields this crate
+    /// doesn't model; it's checked with [`validate_activity_json`] first.
+    pub fn update_presence_json(&mut self, activity: serde_json::Value) -> Receipt {
+        self.handle.update_presence_json(activity)
+    }
+
+    /// Sets the presence from a JSON activity file, as with
+    /// [`update_presence_json`](Self::update_presence_json), and sets it again whenever the file
+    /// changes while events are being polled. Errors while reloading are sent as events.
+    ///
+    /// Only JSON files are supported. Changes are picked up by checking the file's modification
+    /// time at most once a second from [`check_events`](Self::check_events) and the other
+    /// polling methods, so nothing is reloaded while events aren't being polled.
+    #[cfg(feature = "watcher")]
+    pub fn watch_presence_file(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+    ) -> Result<Receipt, Error> {
+        let mut presence_file = watcher::PresenceFileWatcher::new(path.into());
+        let activity = presence_file.load()?;
+        self.presence_file = Some(presence_file);
+        Ok(self.update_presence_json(activity))
+    }
+
+    /// Stops watching the file passed to [`watch_presence_file`](Self::watch_presence_file),
+    /// leaving the presence as it last was.
+    #[cfg(feature = "watcher")]
+    pub fn unwatch_presence_file(&mut self) {
+        self.presence_file = None;
+    }
+
+    #[cfg(feature = "watcher")]
+    fn reload_presence_file(&mut self) {
+        let result = match self.presence_file.as_mut().and_then(|file| file.poll()) {
+            Some(result) => result,
+            None => return,
+        };
+        match result {
+            Ok(activity) => {
+                let _ = self.update_presence_json(activity);
+            }
+            Err(err) => {
+                let _ = self.handle.event_tx.send(Event::GotError(err));
+            }
+        }
+    }
+
+    pub fn update_presence_from(&mut self, value: &impl ToPresence) -> Receipt {
+        self.update_presence(Some(&value.to_presence()))
+    }
+
+    /// Applies `f` to the last presence, or to an empty one if it was cleared, and sends the result.
+    pub fn update_presence_with(&mut self, f: impl FnOnce(&mut Presence)) -> Receipt {
+        let mut presence = self.last_presence().unwrap_or_default();
+        f(&mut presence);
+        self.update_presence(Some(&presence))
+    }
+
+    /// The presence most recently set, whether or not it has been sent yet; `None` after
+    /// [`update_presence_json`](Self::update_presence_json).
+    pub fn last_presence(&self) -> Option<Presence> {
+        self.shared_state.presence.lock().presence.clone()
+    }
+
+    pub fn set_secret_rotation(&mut self, interval: Option<Duration>) {
+        *self.shared_state.secret_rotation.lock() = interval.map(SecretRotation::new);
+    }
+
+    fn rotate_secrets_if_due(&mut self) {
+        match &mut *self.shared_state.secret_rotation.lock() {
+            Some(secret_rotation) if secret_rotation.is_due() => secret_rotation.reset(),
+            _ => return,
+        }
+        let mut presence = match self.last_presence() {
+            Some(presence) => presence,
+            None => return,
+        };
+        let secrets = match presence.secrets.as_mut() {
+            Some(secrets) => secrets,
+            None => return,
+        };
+        if !rotation::rotate(secrets) {
             return;
         }
-        let nonce = self.nonce.next();
-        let _ = self.send_message(&messages::JoinReply {
-            user_id,
-            accepted,
+        let secrets = secrets.clone();
+        let _ = self.handle.publish_presence(Some(presence));
+        let _ = self.handle.event_tx.send(Event::SecretsRotated(secrets));
+    }
+
+    pub fn reply_to_join_request(&mut self, user_id: Snowflake, reply: JoinReply) -> Receipt {
+        self.handle.reply_to_join_request(user_id, reply)
+    }
+
+    /// Automatically answers join requests with `reply` once they've gone unanswered for
+    /// `timeout`, checked whenever events are polled.
+    pub fn set_join_request_timeout(&mut self, timeout: Option<Duration>, reply: JoinReply) {
+        self.join_request_timeout = timeout.map(|timeout| (timeout, reply));
+        if self.join_request_timeout.is_none() {
+            self.shared_state.pending_join_requests.lock().clear();
+        }
+    }
+
+    fn expire_join_requests(&mut self) {
+        let (timeout, reply) = match self.join_request_timeout {
+            Some(join_request_timeout) => join_request_timeout,
+            None => return,
+        };
+        let now = Instant::now();
+        let expired = self
+            .shared_state
+            .pending_join_requests
+            .lock()
+            .iter()
+            .filter(|(_, &requested_at)| now - requested_at >= timeout)
+            .map(|(&user_id, _)| user_id)
+            .collect::<Vec<_>>();
+        for user_id in expired {
+            let _ = self.reply_to_join_request(user_id, reply);
+        }
+    }
+
+    /// Invites a user who hasn't asked to join to the current activity, which needs a join secret
+    /// set in the presence.
+    pub fn invite_to_activity(&mut self, user_id: Snowflake, message: Option<&str>) -> Receipt {
+        if !self.shared_state.is_connected.load(Ordering::Relaxed) {
+            return Receipt::resolved(Err(Error {
+                message: "Not connected".to_string(),
+                code: ErrorCode::NoResponse,
+            }));
+        }
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(
             nonce,
-        });
+            &messages::SendInvite {
+                nonce,
+                user_id,
+                content: message,
+            },
+        )
+    }
+
+    /// Accepts an invite received through the `invite` handler, which makes Discord send the
+    /// activity's join secret through `join_game`.
+    pub fn accept_activity_invite(&mut self, invite: &ActivityInvite) -> Receipt {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::AcceptActivityInvite { nonce, invite })
+    }
+
+    /// Sends an arbitrary command, for those this crate doesn't model yet.
+    pub fn send_command(&mut self, cmd: &str, args: serde_json::Value) -> CommandHandle {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(
+            nonce,
+            &messages::RawCommand {
+                nonce,
+                cmd,
+                args: &args,
+            },
+        )
+    }
+
+    /// Asks the user to authorize the application for `scopes` (e.g. `rpc`, `identify`) through
+    /// Discord's OAuth2 prompt.
+    pub fn authorize(&mut self, scopes: &[&str]) -> Receipt<Authorization> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(
+            nonce,
+            &messages::Authorize {
+                nonce,
+                client_id: &self.app_id,
+                scopes,
+            },
+        )
+    }
+
+    /// Authenticates the connection with an access token obtained from the code returned by
+    /// [`authorize`](Self::authorize).
+    pub fn authenticate(&mut self, access_token: &str) -> Receipt<Authentication> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(
+            nonce,
+            &messages::Authenticate {
+                nonce,
+                access_token,
+            },
+        )
+    }
+
+    /// Fetches the user's current voice settings; requires the `rpc` scope.
+    pub fn get_voice_settings(&mut self) -> Receipt<VoiceSettings> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetVoiceSettings { nonce })
+    }
+
+    /// Applies `settings` to the user's voice settings and resolves with the resulting settings;
+    /// requires the `rpc` scope.
+    pub fn set_voice_settings(&mut self, settings: &VoiceSettingsUpdate) -> Receipt<VoiceSettings> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::SetVoiceSettings { nonce, settings })
+    }
+
+    /// Lists the guilds the user is a member of; requires the `rpc` scope.
+    pub fn get_guilds(&mut self) -> Receipt<GuildList> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetGuilds { nonce })
+    }
+
+    pub fn get_guild(&mut self, guild_id: Snowflake) -> Receipt<Guild> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetGuild { nonce, guild_id })
+    }
+
+    /// Lists the channels of a guild; requires the `rpc` scope.
+    pub fn get_channels(&mut self, guild_id: Snowflake) -> Receipt<ChannelList> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetChannels { nonce, guild_id })
+    }
+
+    pub fn get_channel(&mut self, channel_id: Snowflake) -> Receipt<Channel> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetChannel { nonce, channel_id })
+    }
+
+    /// Fetches the voice channel the user is in, if any; requires the `rpc` scope.
+    pub fn get_selected_voice_channel(&mut self) -> Receipt<SelectedVoiceChannel> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetSelectedVoiceChannel { nonce })
+    }
+
+    /// Starts recording a shortcut through Discord, e.g. for a push-to-talk key, with the keys
+    /// held down being reported to the `shortcut_change` handler until
+    /// [`stop_shortcut_capture`](Self::stop_shortcut_capture) is called; requires the `rpc`
+    /// scope.
+    pub fn start_shortcut_capture(&mut self) -> Receipt {
+        self.shared_state.captured_shortcut.lock().clear();
+        self.modify_subscriptions(|rpc| rpc.capturing_shortcut = true);
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::CaptureShortcut { nonce, start: true })
+    }
+
+    /// Stops recording a shortcut and returns the last combination of keys Discord reported,
+    /// `None` if none was.
+    pub fn stop_shortcut_capture(&mut self) -> Option<Vec<ShortcutKey>> {
+        if !self.capturing_shortcut {
+            return None;
+        }
+        let nonce = self.shared_state.nonce.next();
+        let _: Receipt = self.send_message(
+            nonce,
+            &messages::CaptureShortcut {
+                nonce,
+                start: false,
+            },
+        );
+        self.modify_subscriptions(|rpc| rpc.capturing_shortcut = false);
+        let shortcut = mem::take(&mut *self.shared_state.captured_shortcut.lock());
+        (!shortcut.is_empty()).then_some(shortcut)
+    }
+
+    /// Lists the user's friends, blocked users and pending friend requests; requires the
+    /// `relationships.read` scope.
+    pub fn get_relationships(&mut self) -> Receipt<RelationshipList> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::GetRelationships { nonce })
+    }
+
+    /// Changes how another user is heard locally and resolves with their resulting settings;
+    /// requires the `rpc` scope.
+    pub fn set_user_voice_settings(
+        &mut self,
+        settings: &UserVoiceSettings,
+    ) -> Receipt<UserVoiceSettings> {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::SetUserVoiceSettings { nonce, settings })
+    }
+
+    /// Reports the state of the certified hardware devices connected to the system, replacing
+    /// any previously reported list.
+    pub fn set_certified_devices(&mut self, devices: &[Device]) -> Receipt {
+        let nonce = self.shared_state.nonce.next();
+        self.send_message(nonce, &messages::SetCertifiedDevices { nonce, devices })
+    }
+
+    pub fn set_event_dedup_window(&mut self, window: Option<Duration>) {
+        self.event_dedup = window.map(EventDedup::new);
+    }
+
+    fn next_event(&mut self) -> Option<Event> {
+        loop {
+            let event = self.event_rx.try_recv().ok()?;
+            if let Some(event) = self.filter_event(event) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Drops duplicate events and join requests the policy already answered.
+    fn filter_event(&mut self, event: Event) -> Option<Event> {
+        let is_duplicate = self
+            .event_dedup
+            .as_mut()
+            .is_some_and(|event_dedup| event_dedup.is_duplicate(&event));
+        if is_duplicate {
+            return None;
+        }
+        if let Event::JoinRequested(user) = &event {
+            if let Some(reply) = self.handlers.decide_join_request(user) {
+                let _ = self.reply_to_join_request(user.id, reply);
+                return None;
+            }
+            if self.join_request_timeout.is_some() {
+                self.shared_state
+                    .pending_join_requests
+                    .lock()
+                    .insert(user.id, Instant::now());
+            }
+        }
+        Some(event)
+    }
+
+    fn restart_io_thread_if_dead(&mut self) {
+        match &self.io_thread {
+            Some(io_thread) if io_thread.is_finished() => {}
+            _ => return,
+        }
+        if self.shared_state.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        let _ = self.io_thread.take().unwrap().join();
+        self.shared_state
+            .is_connected
+            .store(false, Ordering::Relaxed);
+        // The dead thread's connection is gone, and with it any response still to come
+        self.shared_state.pending_responses.lock().clear();
+        self.io_thread = Some((self.spawn_io_thread)(
+            &self.app_id,
+            &self.message_rx,
+            &self.handle.event_tx,
+            &self.shared_state,
+        ));
+        let _ = self.handle.event_tx.send(Event::IoThreadRestarted);
+    }
+
+    fn poll_state(&mut self) {
+        self.restart_io_thread_if_dead();
+        self.rotate_secrets_if_due();
+        self.expire_join_requests();
+        #[cfg(feature = "watcher")]
+        self.reload_presence_file();
     }
 
     pub fn check_events(&mut self) {
-        macro_rules! run_cb {
-            ($callback: expr, $($args: tt)*) => {
-                if let Some(callback) = &mut $callback {
-                    callback($($args)*);
+        self.poll_state();
+        while let Some(event) = self.next_event() {
+            self.handlers.dispatch(event);
+        }
+    }
+
+    pub fn check_events_for(&mut self, time_budget: Duration) {
+        let deadline = Instant::now() + time_budget;
+        self.poll_state();
+        while Instant::now() < deadline {
+            match self.next_event() {
+                Some(event) => self.handlers.dispatch(event),
+                None => break,
+            }
+        }
+    }
+
+    pub fn check_events_budget(&mut self, max_events: usize) {
+        self.poll_state();
+        for _ in 0..max_events {
+            match self.next_event() {
+                Some(event) => self.handlers.dispatch(event),
+                None => break,
+            }
+        }
+    }
+
+    pub fn try_recv_event(&mut self) -> Option<Event> {
+        self.poll_state();
+        self.next_event()
+    }
+
+    /// Blocks until an event arrives or `timeout` elapses, waiting indefinitely if it's `None`.
+    pub fn wait_event(&mut self, timeout: Option<Duration>) -> Option<Event> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            self.poll_state();
+            // Wake up periodically to keep restarting the IO thread, rotating secrets and
+            // expiring join requests
+            let mut wait = MAX_IO_THREAD_TIMEOUT;
+            if let Some(deadline) = deadline {
+                wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+            }
+            match self.event_rx.recv_timeout(wait) {
+                Ok(event) => {
+                    if let Some(event) = self.filter_event(event) {
+                        return Some(event);
+                    }
                 }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
             }
         }
-        for event in self.event_rx.try_iter() {
-            match event {
-                Event::Connected(user) => run_cb!(self.handlers.connect, user),
-                Event::Disconnected(err) => run_cb!(self.handlers.disconnect, err),
-                Event::GotError(err) => run_cb!(self.handlers.error, err),
-                Event::GameJoined(secret) => run_cb!(self.handlers.join_game, secret),
-                Event::StartedSpectating(secret) => run_cb!(self.handlers.spectate_game, secret),
-                Event::JoinRequested(user) => run_cb!(self.handlers.join_request, user),
+    }
+
+    /// Like [`check_events`](Self::check_events), but first waits for an event to arrive for up
+    /// to `timeout`, or indefinitely if it's `None`.
+    pub fn check_events_blocking(&mut self, timeout: Option<Duration>) {
+        if let Some(event) = self.wait_event(timeout) {
+            self.handlers.dispatch(event);
+            while let Some(event) = self.next_event() {
+                self.handlers.dispatch(event);
+            }
+        }
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.poll_state();
+        iter::from_fn(move || self.next_event())
+    }
+
+    pub fn has_pending_events(&self) -> bool {
+        !self.event_rx.is_empty()
+    }
+
+    /// Stops the client, closing the connection gracefully. Returns the first error that hadn't
+    /// been dispatched yet, or a `SHUTDOWN_TIMEOUT` error if the IO thread didn't exit in time.
+    pub fn shutdown(mut self, options: ShutdownOptions) -> Result<(), Error> {
+        if options.clear_presence && self.shared_state.is_connected.load(Ordering::Relaxed) {
+            let _ = self.update_presence(None);
+        }
+        self.shared_state.stopped.store(true, Ordering::Relaxed);
+        let mut result = Ok(());
+        if let Some(thread) = self.io_thread.take() {
+            thread.thread().unpark();
+            let deadline = Instant::now() + options.timeout;
+            while !thread.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+            if thread.is_finished() {
+                let _ = thread.join();
+            } else {
+                result = Err(Error {
+                    message: "The IO thread didn't stop in time".to_string(),
+                    code: ErrorCode::ShutdownTimeout,
+                });
             }
         }
+        let pending_error = self.event_rx.try_iter().find_map(|event| match event {
+            Event::GotError(err) => Some(err),
+            _ => None,
+        });
+        match pending_error {
+            Some(err) => Err(err),
+            None => result,
+        }
     }
 }
 
 impl Drop for Rpc {
     fn drop(&mut self) {
-        self.shared_state
-            .presence_updated
-            .store(false, Ordering::Relaxed);
+        // The IO thread flushes the cleared activity before closing the connection
+        if self.clear_presence_on_drop
+            && self.io_thread.is_some()
+            && self.shared_state.is_connected.load(Ordering::Relaxed)
+        {
+            let _ = self.handle.publish_presence(None);
+        } else {
+            self.shared_state.presence.lock().pending = false;
+        }
         self.shared_state.stopped.store(true, Ordering::Relaxed);
         if let Some(thread) = self.io_thread.take() {
             thread.thread().unpark();
@@ -216,15 +1153,27 @@ impl Drop for Rpc {
 }
 
 struct ReconnectionTime {
+    policy: ReconnectPolicy,
     backoff: Backoff,
     next_time: Instant,
+    attempts: u32,
+    gave_up: bool,
 }
 
 impl ReconnectionTime {
-    fn new() -> Self {
+    fn new(policy: ReconnectPolicy) -> Self {
         ReconnectionTime {
-            backoff: Backoff::new(Duration::from_millis(500), Duration::from_secs(60)),
+            policy,
+            backoff: Backoff::new(policy.min_delay, policy.max_delay, policy.jitter),
             next_time: Instant::now(),
+            attempts: 0,
+            gave_up: false,
+        }
+    }
+
+    fn set_policy(&mut self, policy: ReconnectPolicy) {
+        if policy != self.policy {
+            *self = ReconnectionTime::new(policy);
         }
     }
 
@@ -232,42 +1181,251 @@ impl ReconnectionTime {
         let delay = self.backoff.next();
         self.next_time = Instant::now() + delay;
     }
+
+    fn reset(&mut self) {
+        *self = ReconnectionTime::new(self.policy);
+    }
+}
+
+type IoThreadSpawner = Box<
+    dyn Fn(&str, &Receiver<Vec<u8>>, &Sender<Event>, &Arc<SharedState>) -> JoinHandle<()> + Send,
+>;
+
+fn spawn_io_thread<C: Connector>(
+    connector: C,
+    app_id: &str,
+    message_rx: &Receiver<Vec<u8>>,
+    event_tx: &Sender<Event>,
+    shared_state: &Arc<SharedState>,
+) -> JoinHandle<()> {
+    let app_id = app_id.to_string();
+    let message_rx = message_rx.clone();
+    let event_tx = event_tx.clone();
+    let shared_state = Arc::clone(shared_state);
+    let io_thread = thread::Builder::new()
+        .name("Discord RPC".to_string())
+        .spawn({
+            let shared_state = Arc::clone(&shared_state);
+            move || run_io_thread(connector, app_id, message_rx, event_tx, shared_state)
+        })
+        .expect("Couldn't spawn Discord RPC IO thread");
+    *shared_state.io_thread.lock() = Some(io_thread.thread().clone());
+    io_thread
+}
+
+/// Flags the last published presence for sending again under a fresh nonce, as Discord forgets
+/// the activity along with the connection.
+fn resend_last_presence(shared_state: &SharedState) {
+    let mut update = shared_state.presence.lock();
+    // A presence that hasn't been sent yet keeps its nonce so its receipt still resolves
+    if update.nonce.is_none() || update.pending {
+        return;
+    }
+    update.nonce = Some(shared_state.nonce.next());
+    update.pending = true;
+}
+
+fn report_rpc_error(shared_state: &SharedState, event_tx: &Sender<Event>, err: RpcError) {
+    shared_state.stats.lock().last_error = Some(err.clone().into());
+    let _ = event_tx.send(Event::GotRpcError(err));
 }
 
-fn run_io_thread(
+fn run_io_thread<C: Connector>(
+    connector: C,
     app_id: String,
     message_rx: Receiver<Vec<u8>>,
     event_tx: Sender<Event>,
     shared_state: Arc<SharedState>,
 ) {
-    let mut connection = Connection::new(app_id);
-    let reconnection_time = Rc::new(RefCell::new(ReconnectionTime::new()));
-    
+    let mut connection = Connection::new(connector, app_id);
+    connection.traffic = shared_state.stats.lock().traffic;
+    let reconnection_time = Rc::new(RefCell::new(ReconnectionTime::new(
+        *shared_state.reconnect_policy.lock(),
+    )));
+    let resend_presence = Rc::new(Cell::new(false));
+    let mut presence_rate_limiter = RateLimiter::new();
+
     {
         let event_tx = event_tx.clone();
+        let shared_state = Arc::clone(&shared_state);
         let reconnection_time = Rc::clone(&reconnection_time);
-        connection.on_connect = Some(Box::new(move |user| {
-            event_tx.send(Event::Connected(user)).unwrap();
-            reconnection_time.borrow_mut().backoff.reset();
+        let resend_presence = Rc::clone(&resend_presence);
+        connection.on_connect = Some(Box::new(move |ready| {
+            resend_presence.set(true);
+            shared_state.current_user.lock().clone_from(&ready.user);
+            *shared_state.status.lock() = ConnectionStatus::Connected {
+                since: Instant::now(),
+            };
+            *shared_state.server_config.lock() = ready.config;
+            {
+                let mut stats = shared_state.stats.lock();
+                if stats.has_connected {
+                    stats.reconnect_count += 1;
+                }
+                stats.has_connected = true;
+            }
+            // Subscriptions don't survive the connection, so replay the whole desired set
+            let subscriptions = shared_state.subscriptions.lock();
+            *shared_state.subscription_changes.lock() = subscriptions
+                .iter()
+                .map(|&subscription| (subscription, true))
+                .collect();
+            drop(subscriptions);
+            event_tx.send(Event::Connected(ready.user)).unwrap();
+            let mut reconnection_time = reconnection_time.borrow_mut();
+            reconnection_time.backoff.reset();
+            reconnection_time.attempts = 0;
         }));
     }
 
     {
         let event_tx = event_tx.clone();
+        let shared_state = Arc::clone(&shared_state);
         let reconnection_time = Rc::clone(&reconnection_time);
-        connection.on_disconnect = Some(Box::new(move |err| {
-            event_tx.send(Event::Disconnected(err.cloned())).unwrap();
+        connection.on_disconnect = Some(Box::new(move |reason| {
+            // Responses can't arrive on a new connection, so resolve outstanding receipts now
+            shared_state.pending_responses.lock().clear();
+            if let Some(err) = reason.error() {
+                shared_state.stats.lock().last_error = Some(err.clone());
+            }
+            event_tx.send(Event::Disconnected(reason.clone())).unwrap();
             reconnection_time.borrow_mut().calc_next();
         }));
     }
 
+    let mut ping_sent_at = None;
+    let mut was_disabled = false;
     while !shared_state.stopped.load(Ordering::Relaxed) {
+        if !shared_state.enabled.load(Ordering::Relaxed) {
+            if !mem::replace(&mut was_disabled, true) {
+                if connection.is_connected() {
+                    let frame = PresenceUpdate {
+                        nonce: Some(shared_state.nonce.next()),
+                        pid: shared_state.pid.load(Ordering::Relaxed),
+                        ..Default::default()
+                    }
+                    .to_frame();
+                    if let Some(frame) = frame {
+                        let _ = connection.write_raw(&frame);
+                    }
+                }
+                connection.close_gracefully(DisconnectReason::Disabled);
+                ping_sent_at = None;
+                shared_state.pending_responses.lock().clear();
+                shared_state.is_connected.store(false, Ordering::Relaxed);
+                *shared_state.status.lock() = ConnectionStatus::Disabled;
+            }
+            // Commands sent while disabled are dropped, so only their own receipts are resolved
+            for message in message_rx.try_iter() {
+                if let Some(nonce) = messages::nonce_of(&message) {
+                    shared_state.pending_responses.lock().remove(&nonce);
+                }
+            }
+            thread::park_timeout(MAX_IO_THREAD_TIMEOUT);
+            continue;
+        }
+        if mem::take(&mut was_disabled) {
+            reconnection_time.borrow_mut().reset();
+        }
+        {
+            let app_id = shared_state.app_id.lock();
+            if *app_id != connection.app_id {
+                connection.app_id.clone_from(&app_id);
+                drop(app_id);
+                connection.close_gracefully(DisconnectReason::AppIdChanged);
+                reconnection_time.borrow_mut().reset();
+            }
+        }
+
         if connection.is_connected() {
             while let Ok(Some(mut message)) = connection.read_json::<messages::Event>() {
+                let pending = message
+                    .nonce
+                    .and_then(|nonce| shared_state.pending_responses.lock().remove(&nonce));
+                match pending {
+                    Some(PendingResponse::Subscription(subscription, enabled))
+                        if message.command == "SUBSCRIBE" || message.command == "UNSUBSCRIBE" =>
+                    {
+                        let error = if message.event == "ERROR" {
+                            Some(
+                                serde_json::from_value::<RpcError>(message.data.into())
+                                    .unwrap_or_else(|err| RpcError {
+                                        code: RpcErrorCode::UnknownError.code(),
+                                        message: err.to_string(),
+                                    }),
+                            )
+                        } else {
+                            None
+                        };
+                        let event = subscription.event;
+                        if enabled {
+                            let mut capabilities = shared_state.capabilities.lock();
+                            if let Some(capability) = capabilities.for_event_mut(event) {
+                                if let Some(err) = &error {
+                                    if *capability != Capability::Rejected {
+                                        *capability = Capability::Rejected;
+                                        report_rpc_error(
+                                            &shared_state,
+                                            &event_tx,
+                                            RpcError {
+                                                code: err.code,
+                                                message: format!(
+                                                    "Discord rejected the {} subscription, the \
+                                                     application may not be approved for this \
+                                                     feature: {}",
+                                                    event, err.message
+                                                ),
+                                            },
+                                        );
+                                    }
+                                } else {
+                                    *capability = Capability::Available;
+                                }
+                            }
+                        }
+                        let _ = event_tx.send(Event::SubscriptionUpdated(SubscriptionUpdate {
+                            subscription,
+                            enabled,
+                            error,
+                        }));
+                        continue;
+                    }
+                    Some(PendingResponse::Command(response_tx)) => {
+                        if message.event != "ERROR" {
+                            let _ = response_tx.send(Ok(message.data));
+                            continue;
+                        }
+                        match serde_json::from_value::<RpcError>(message.data.into()) {
+                            Ok(err) => {
+                                // Only reported as an event if nobody kept the receipt
+                                if response_tx.send(Err(err.clone().into())).is_err() {
+                                    report_rpc_error(&shared_state, &event_tx, err);
+                                }
+                            }
+                            Err(err) => {
+                                let _ = response_tx.send(Err(Error {
+                                    message: err.to_string(),
+                                    code: ErrorCode::ReadCorrupt,
+                                }));
+                            }
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                if message.command == "DISPATCH" && shared_state.raw_events.load(Ordering::Relaxed)
+                {
+                    let _ = event_tx.send(Event::Raw(
+                        message.event.clone(),
+                        serde_json::Value::Object(message.data.clone()),
+                    ));
+                }
+
                 match message.event.as_str() {
                     "ERROR" => {
-                        if let Ok(err) = serde_json::from_value::<Error>(message.data.into()) {
-                            let _ = event_tx.send(Event::GotError(err));
+                        if let Ok(err) = serde_json::from_value::<RpcError>(message.data.into()) {
+                            report_rpc_error(&shared_state, &event_tx, err);
                         }
                     }
 
@@ -301,26 +1459,206 @@ fn run_io_thread(
                         }
                     }
 
+                    "ACTIVITY_INVITE" => {
+                        if let Ok(invite) =
+                            serde_json::from_value::<ActivityInvite>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::InviteReceived(invite));
+                        }
+                    }
+
+                    "RELATIONSHIP_UPDATE" => {
+                        if let Ok(relationship) =
+                            serde_json::from_value::<Relationship>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::RelationshipUpdated(relationship));
+                        }
+                    }
+
+                    "VOICE_CHANNEL_SELECT" => {
+                        if let Ok(selection) =
+                            serde_json::from_value::<VoiceChannelSelect>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::VoiceChannelSelected(selection));
+                        }
+                    }
+
+                    "VOICE_SETTINGS_UPDATE" => {
+                        if let Ok(settings) =
+                            serde_json::from_value::<VoiceSettings>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::VoiceSettingsUpdated(settings));
+                        }
+                    }
+
+                    "GUILD_CREATE" => {
+                        if let Ok(guild) = serde_json::from_value::<Guild>(message.data.into()) {
+                            let _ = event_tx.send(Event::GuildCreated(guild));
+                        }
+                    }
+
+                    "GUILD_STATUS" => {
+                        if let Ok(status) =
+                            serde_json::from_value::<GuildStatus>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::GuildStatusUpdated(status));
+                        }
+                    }
+
+                    "CAPTURE_SHORTCUT_CHANGE" => {
+                        if let Ok(change) =
+                            serde_json::from_value::<messages::ShortcutChange>(message.data.into())
+                        {
+                            shared_state
+                                .captured_shortcut
+                                .lock()
+                                .clone_from(&change.shortcut);
+                            let _ = event_tx.send(Event::ShortcutChanged(change.shortcut));
+                        }
+                    }
+
+                    "CHANNEL_CREATE" => {
+                        if let Ok(channel) = serde_json::from_value::<Channel>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::ChannelCreated(channel));
+                        }
+                    }
+
+                    "OVERLAY_UPDATE" => {
+                        if let Ok(overlay) =
+                            serde_json::from_value::<OverlayState>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::OverlayUpdated(overlay));
+                        }
+                    }
+
+                    "NOTIFICATION_CREATE" => {
+                        if let Ok(notification) =
+                            serde_json::from_value::<Notification>(message.data.into())
+                        {
+                            let _ = event_tx.send(Event::NotificationCreated(notification));
+                        }
+                    }
+
+                    "VOICE_STATE_CREATE" | "VOICE_STATE_UPDATE" | "VOICE_STATE_DELETE" => {
+                        if let Ok(state) = serde_json::from_value::<VoiceState>(message.data.into())
+                        {
+                            let _ = event_tx.send(match message.event.as_str() {
+                                "VOICE_STATE_CREATE" => Event::VoiceStateCreated(state),
+                                "VOICE_STATE_UPDATE" => Event::VoiceStateUpdated(state),
+                                _ => Event::VoiceStateDeleted(state),
+                            });
+                        }
+                    }
+
+                    "SPEAKING_START" | "SPEAKING_STOP" => {
+                        if let Ok(speaking) =
+                            serde_json::from_value::<Speaking>(message.data.into())
+                        {
+                            let _ = event_tx.send(if message.event == "SPEAKING_START" {
+                                Event::SpeakingStarted(speaking)
+                            } else {
+                                Event::SpeakingStopped(speaking)
+                            });
+                        }
+                    }
+
                     _ => {}
                 }
             }
 
-            if shared_state
-                .presence_updated
-                .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-                .is_ok()
-            {
-                let _ = connection.write_raw(&shared_state.presence.lock()[..]);
+            if resend_presence.take() {
+                resend_last_presence(&shared_state);
+            }
+
+            // Updates held back by the rate limit stay pending, so only the latest one goes out
+            let frame = {
+                let mut update = shared_state.presence.lock();
+                if update.pending
+                    && presence_rate_limiter.try_acquire(*shared_state.presence_rate_limit.lock())
+                {
+                    update.pending = false;
+                    update.to_frame()
+                } else {
+                    None
+                }
+            };
+            if let Some(frame) = frame {
+                let _ = connection.write_raw(&frame);
+            }
+
+            let subscription_changes = mem::take(&mut *shared_state.subscription_changes.lock());
+            for (subscription, enabled) in subscription_changes {
+                let nonce = shared_state.nonce.next();
+                shared_state
+                    .pending_responses
+                    .lock()
+                    .insert(nonce, PendingResponse::Subscription(subscription, enabled));
+                if let Ok(message) = serde_json::to_vec(&messages::ToggleSubscription {
+                    nonce,
+                    event: subscription.event,
+                    channel_id: subscription.channel_id,
+                    guild_id: subscription.guild_id,
+                    enabled,
+                }) {
+                    let _ = connection.write_raw(&message);
+                }
             }
 
             for message in message_rx.try_iter() {
                 let _ = connection.write_raw(&message);
             }
+
+            // Any frame proves Discord is alive, so only PING after a silence
+            if let Some(keepalive) = *shared_state.keepalive.lock() {
+                let last_received = connection.traffic.last_frame_received_at;
+                match ping_sent_at {
+                    Some(sent_at) if last_received.is_some_and(|received| received >= sent_at) => {
+                        ping_sent_at = None;
+                    }
+                    Some(sent_at) => {
+                        if sent_at.elapsed() >= keepalive.timeout {
+                            ping_sent_at = None;
+                            connection.close_unresponsive();
+                        }
+                    }
+                    None => {
+                        if last_received
+                            .is_none_or(|received| received.elapsed() >= keepalive.interval)
+                            && connection.ping().is_ok()
+                        {
+                            ping_sent_at = Some(Instant::now());
+                        }
+                    }
+                }
+            } else {
+                ping_sent_at = None;
+            }
         } else {
+            ping_sent_at = None;
             let mut reconnection_time = reconnection_time.borrow_mut();
-            if Instant::now() >= reconnection_time.next_time {
+            reconnection_time.set_policy(*shared_state.reconnect_policy.lock());
+            if !connection.is_handshaking()
+                && !reconnection_time.gave_up
+                && reconnection_time
+                    .policy
+                    .max_attempts
+                    .is_some_and(|max_attempts| reconnection_time.attempts >= max_attempts)
+            {
+                reconnection_time.gave_up = true;
+                let _ = event_tx.send(Event::Disconnected(DisconnectReason::GaveUp {
+                    attempts: reconnection_time.attempts,
+                }));
+            }
+            // A handshake in progress is polled for its reply (or timeout) on every pass
+            if connection.is_handshaking() {
+                drop(reconnection_time);
+                let _ = connection.open();
+            } else if !reconnection_time.gave_up && Instant::now() >= reconnection_time.next_time {
+                reconnection_time.attempts += 1;
                 reconnection_time.calc_next();
                 drop(reconnection_time);
+                connection.ipc_config = shared_state.ipc_config.lock().clone();
                 let _ = connection.open();
             }
         }
@@ -328,6 +1666,44 @@ fn run_io_thread(
         shared_state
             .is_connected
             .store(connection.is_connected(), Ordering::Relaxed);
+        {
+            let mut stats = shared_state.stats.lock();
+            stats.traffic = connection.traffic;
+            stats.pipe_index = connection.pipe_index();
+            stats.backoff_delay = reconnection_time.borrow().backoff.current_delay;
+        }
+        if !connection.is_connected() {
+            let reconnection_time = reconnection_time.borrow();
+            *shared_state.status.lock() = if reconnection_time.gave_up {
+                ConnectionStatus::Disconnected {
+                    last_error: shared_state.stats.lock().last_error.clone(),
+                }
+            } else {
+                ConnectionStatus::Reconnecting {
+                    next_attempt: reconnection_time.next_time,
+                    attempts: reconnection_time.attempts,
+                }
+            };
+        }
         thread::park_timeout(MAX_IO_THREAD_TIMEOUT);
     }
+
+    // Flush a presence queued right before shutting down, i.e. the final cleared activity
+    if connection.is_connected() {
+        let frame = {
+            let mut update = shared_state.presence.lock();
+            if mem::take(&mut update.pending) {
+                update.to_frame()
+            } else {
+                None
+            }
+        };
+        if let Some(frame) = frame {
+            let _ = connection.write_raw(&frame);
+        }
+    }
+    connection.close();
+    *shared_state.status.lock() = ConnectionStatus::Disconnected {
+        last_error: shared_state.stats.lock().last_error.clone(),
+    };
 }
@@ -0,0 +1,116 @@
+/// Builds a [`Presence`](crate::Presence) from `field: value` pairs, checking it with
+/// [`validate_presence`](crate::validate_presence) like
+/// [`PresenceBuilder::build`](crate::PresenceBuilder::build) does.
+#[macro_export]
+macro_rules! presence {
+    (@text $value: expr) => {
+        ::core::convert::Into::<String>::into($value)
+    };
+
+    (@field $presence: ident, kind, $value: expr) => {
+        $presence.kind = $value;
+    };
+    (@field $presence: ident, state, $value: expr) => {
+        $presence.state = ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, details, $value: expr) => {
+        $presence.details = ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, start, $value: expr) => {
+        $presence.timestamps.get_or_insert_with(::core::default::Default::default).start = ::core::option::Option::Some($crate::Timestamp::from($value));
+    };
+    (@field $presence: ident, end, $value: expr) => {
+        $presence.timestamps.get_or_insert_with(::core::default::Default::default).end = ::core::option::Option::Some($crate::Timestamp::from($value));
+    };
+    (@field $presence: ident, large_image, $value: expr) => {
+        $presence.images.get_or_insert_with(::core::default::Default::default).large_key =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, large_text, $value: expr) => {
+        $presence.images.get_or_insert_with(::core::default::Default::default).large_text =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, small_image, $value: expr) => {
+        $presence.images.get_or_insert_with(::core::default::Default::default).small_key =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, small_text, $value: expr) => {
+        $presence.images.get_or_insert_with(::core::default::Default::default).small_text =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, party_id, $value: expr) => {
+        $presence.party.get_or_insert_with(::core::default::Default::default).id =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, party, $value: expr) => {{
+        let (size, max): (u32, u32) = $value;
+        let party = $presence.party.get_or_insert_with(::core::default::Default::default);
+        party.size = ::core::option::Option::Some(size);
+        party.max = ::core::option::Option::Some(max);
+    }};
+    (@field $presence: ident, match_secret, $value: expr) => {
+        $presence.secrets.get_or_insert_with(::core::default::Default::default).match_ =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, join_secret, $value: expr) => {
+        $presence.secrets.get_or_insert_with(::core::default::Default::default).join =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+    (@field $presence: ident, spectate_secret, $value: expr) => {
+        $presence.secrets.get_or_insert_with(::core::default::Default::default).spectate =
+            ::core::option::Option::Some($crate::presence!(@text $value));
+    };
+
+    ($($field: ident: $value: expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut presence = $crate::Presence::default();
+        $($crate::presence!(@field presence, $field, $value);)*
+        $crate::validate_presence(&presence).map(|()| presence)
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Presence, PresenceError};
+
+    #[test]
+    fn builds_like_the_builder() {
+        let presence = presence! {
+            state: "In a match",
+            details: "Ranked",
+            large_image: "map",
+            party_id: "party",
+            party: (2, 4),
+        };
+        let built = Presence::builder()
+            .state("In a match")
+            .details("Ranked")
+            .large_image("map")
+            .party_id("party")
+            .party_size(2, 4)
+            .build();
+        assert_eq!(presence, built);
+    }
+
+    #[test]
+    fn rejects_invalid_presences() {
+        assert_eq!(
+            presence! { state: "a" },
+            Err(PresenceError::TooShort {
+                field: "state",
+                min_len: 2
+            })
+        );
+        assert_eq!(
+            presence! { large_image: "a".repeat(33) },
+            Err(PresenceError::TooLong {
+                field: "large_image",
+                max_len: 32
+            })
+        );
+        assert_eq!(
+            presence! { party_id: "party", party: (5, 4) },
+            Err(PresenceError::PartySizeExceedsMax { size: 5, max: 4 })
+        );
+    }
+}
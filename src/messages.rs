@@ -1,22 +1,25 @@
-use super::{Presence, User};
-use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use super::{
+    ActivityInvite, Device, Message, Presence, ShortcutKey, Snowflake, User, UserVoiceSettings,
+    VoiceSettingsUpdate,
+};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, Copy, Debug)]
-pub struct SetActivity<'a> {
+pub struct SetActivity<'a, A = Presence> {
     pub pid: u32,
     pub nonce: i32,
-    pub presence: Option<&'a Presence>,
+    pub activity: Option<&'a A>,
 }
 
-impl<'a> Serialize for SetActivity<'a> {
+impl<'a, A: Serialize> Serialize for SetActivity<'a, A> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        struct Args<'a>(&'a SetActivity<'a>);
+        struct Args<'a, A>(&'a SetActivity<'a, A>);
 
-        impl<'a> Serialize for Args<'a> {
+        impl<'a, A: Serialize> Serialize for Args<'a, A> {
             fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
                 let mut obj = serializer.serialize_map(None)?;
                 obj.serialize_entry("pid", &self.0.pid)?;
-                if let Some(activity) = &self.0.presence {
+                if let Some(activity) = &self.0.activity {
                     obj.serialize_entry("activity", activity)?;
                 }
                 obj.end()
@@ -47,36 +50,61 @@ impl<'a> Serialize for Handshake<'a> {
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct ToggleSubscription<'a, const ENABLED: bool> {
+pub struct ToggleSubscription<'a> {
     pub nonce: i32,
     pub event: &'a str,
+    pub channel_id: Option<Snowflake>,
+    pub guild_id: Option<Snowflake>,
+    pub enabled: bool,
 }
 
-impl<'a, const ENABLED: bool> Serialize for ToggleSubscription<'a, ENABLED> {
+impl<'a> Serialize for ToggleSubscription<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut obj = serializer.serialize_map(Some(3))?;
-        obj.serialize_entry("cmd", if ENABLED { "SUBSCRIBE" } else { "UNSUBSCRIBE" })?;
+        struct Args(&'static str, Snowflake);
+
+        impl Serialize for Args {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry(self.0, &self.1)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(None)?;
+        obj.serialize_entry(
+            "cmd",
+            if self.enabled {
+                "SUBSCRIBE"
+            } else {
+                "UNSUBSCRIBE"
+            },
+        )?;
         obj.serialize_entry("nonce", &self.nonce)?;
-        obj.serialize_entry("event", self.event)?;
+        obj.serialize_entry("evt", self.event)?;
+        if let Some(channel_id) = self.channel_id {
+            obj.serialize_entry("args", &Args("channel_id", channel_id))?;
+        } else if let Some(guild_id) = self.guild_id {
+            obj.serialize_entry("args", &Args("guild_id", guild_id))?;
+        }
         obj.end()
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct JoinReply<'a> {
+pub struct JoinRequestReply {
     pub nonce: i32,
     pub accepted: bool,
-    pub user_id: &'a str,
+    pub user_id: Snowflake,
 }
 
-impl<'a> Serialize for JoinReply<'a> {
+impl Serialize for JoinRequestReply {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        struct Args<'a>(&'a JoinReply<'a>);
+        struct Args<'a>(&'a JoinRequestReply);
 
         impl<'a> Serialize for Args<'a> {
             fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
                 let mut obj = serializer.serialize_map(None)?;
-                obj.serialize_entry("user_id", self.0.user_id)?;
+                obj.serialize_entry("user_id", &self.0.user_id)?;
                 obj.end()
             }
         }
@@ -96,6 +124,405 @@ impl<'a> Serialize for JoinReply<'a> {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct RawCommand<'a> {
+    pub nonce: i32,
+    pub cmd: &'a str,
+    pub args: &'a serde_json::Value,
+}
+
+impl<'a> Serialize for RawCommand<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(None)?;
+        obj.serialize_entry("cmd", self.cmd)?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        if !self.args.is_null() {
+            obj.serialize_entry("args", self.args)?;
+        }
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Authorize<'a> {
+    pub nonce: i32,
+    pub client_id: &'a str,
+    pub scopes: &'a [&'a str],
+}
+
+impl<'a> Serialize for Authorize<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a Authorize<'a>);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(None)?;
+                obj.serialize_entry("client_id", self.0.client_id)?;
+                obj.serialize_entry("scopes", self.0.scopes)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "AUTHORIZE")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Authenticate<'a> {
+    pub nonce: i32,
+    pub access_token: &'a str,
+}
+
+impl<'a> Serialize for Authenticate<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a Authenticate<'a>);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(None)?;
+                obj.serialize_entry("access_token", self.0.access_token)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "AUTHENTICATE")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetVoiceSettings {
+    pub nonce: i32,
+}
+
+impl Serialize for GetVoiceSettings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(2))?;
+        obj.serialize_entry("cmd", "GET_VOICE_SETTINGS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SetVoiceSettings<'a> {
+    pub nonce: i32,
+    pub settings: &'a VoiceSettingsUpdate,
+}
+
+impl<'a> Serialize for SetVoiceSettings<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "SET_VOICE_SETTINGS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", self.settings)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetGuilds {
+    pub nonce: i32,
+}
+
+impl Serialize for GetGuilds {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(2))?;
+        obj.serialize_entry("cmd", "GET_GUILDS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetSelectedVoiceChannel {
+    pub nonce: i32,
+}
+
+impl Serialize for GetSelectedVoiceChannel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(2))?;
+        obj.serialize_entry("cmd", "GET_SELECTED_VOICE_CHANNEL")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CaptureShortcut {
+    pub nonce: i32,
+    pub start: bool,
+}
+
+impl Serialize for CaptureShortcut {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args(bool);
+
+        impl Serialize for Args {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry("action", if self.0 { "START" } else { "STOP" })?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "CAPTURE_SHORTCUT")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self.start))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShortcutChange {
+    #[serde(default)]
+    pub shortcut: Vec<ShortcutKey>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetRelationships {
+    pub nonce: i32,
+}
+
+impl Serialize for GetRelationships {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(2))?;
+        obj.serialize_entry("cmd", "GET_RELATIONSHIPS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetGuild {
+    pub nonce: i32,
+    pub guild_id: Snowflake,
+}
+
+impl Serialize for GetGuild {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a GetGuild);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry("guild_id", &self.0.guild_id)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "GET_GUILD")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetChannels {
+    pub nonce: i32,
+    pub guild_id: Snowflake,
+}
+
+impl Serialize for GetChannels {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a GetChannels);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry("guild_id", &self.0.guild_id)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "GET_CHANNELS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GetChannel {
+    pub nonce: i32,
+    pub channel_id: Snowflake,
+}
+
+impl Serialize for GetChannel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a GetChannel);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry("channel_id", &self.0.channel_id)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "GET_CHANNEL")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SendInvite<'a> {
+    pub nonce: i32,
+    pub user_id: Snowflake,
+    pub content: Option<&'a str>,
+}
+
+impl<'a> Serialize for SendInvite<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a SendInvite<'a>);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(None)?;
+                obj.serialize_entry("user_id", &self.0.user_id)?;
+                if let Some(content) = self.0.content {
+                    obj.serialize_entry("content", content)?;
+                }
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "SEND_ACTIVITY_JOIN_INVITE")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AcceptActivityInvite<'a> {
+    pub nonce: i32,
+    pub invite: &'a ActivityInvite,
+}
+
+impl<'a> Serialize for AcceptActivityInvite<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a ActivityInvite);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(5))?;
+                // Only join invites exist
+                obj.serialize_entry("type", &1)?;
+                obj.serialize_entry("user_id", &self.0.user.id)?;
+                obj.serialize_entry("session_id", &self.0.activity.session_id)?;
+                obj.serialize_entry("channel_id", &self.0.channel_id)?;
+                obj.serialize_entry("message_id", &self.0.message_id)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "ACCEPT_ACTIVITY_INVITE")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self.invite))?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SetUserVoiceSettings<'a> {
+    pub nonce: i32,
+    pub settings: &'a UserVoiceSettings,
+}
+
+impl<'a> Serialize for SetUserVoiceSettings<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "SET_USER_VOICE_SETTINGS")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", self.settings)?;
+        obj.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SetCertifiedDevices<'a> {
+    pub nonce: i32,
+    pub devices: &'a [Device],
+}
+
+impl<'a> Serialize for SetCertifiedDevices<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Args<'a>(&'a SetCertifiedDevices<'a>);
+
+        impl<'a> Serialize for Args<'a> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut obj = serializer.serialize_map(Some(1))?;
+                obj.serialize_entry("devices", self.0.devices)?;
+                obj.end()
+            }
+        }
+
+        let mut obj = serializer.serialize_map(Some(3))?;
+        obj.serialize_entry("cmd", "SET_CERTIFIED_DEVICES")?;
+        obj.serialize_entry("nonce", &self.nonce)?;
+        obj.serialize_entry("args", &Args(self))?;
+        obj.end()
+    }
+}
+
+/// The response to `AUTHORIZE`: an OAuth2 code to exchange for an access token through
+/// Discord's token endpoint, which needs the application's client secret.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Authorization {
+    pub code: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Application {
+    pub id: Snowflake,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub rpc_origins: Vec<String>,
+}
+
+/// The response to `AUTHENTICATE`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Authentication {
+    pub user: User,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub expires: String,
+    pub application: Application,
+}
+
+/// The payload of `NOTIFICATION_CREATE`, sent when the user receives a message notification.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Notification {
+    pub channel_id: Snowflake,
+    pub message: Message,
+    #[serde(default)]
+    pub icon_url: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct HandshakeReply {
     #[serde(rename = "cmd")]
@@ -108,11 +535,54 @@ pub struct HandshakeReply {
 #[derive(Clone, Debug, Deserialize)]
 pub struct HandshakeReplyData {
     pub user: Option<User>,
+    pub config: Option<ServerConfig>,
+}
+
+/// The `config` block of the `READY` event, describing the Discord client's environment.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub cdn_host: String,
+    pub api_endpoint: String,
+    /// Usually `production`, even on Canary and PTB, which `api_endpoint` tells apart instead.
+    pub environment: String,
+}
+
+fn deserialize_nullable<'de, D: Deserializer<'de>, T: Default + Deserialize<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+fn deserialize_nonce<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::Number(nonce)) => nonce.as_i64().map(|nonce| nonce as i32),
+            Some(serde_json::Value::String(nonce)) => nonce.parse().ok(),
+            _ => None,
+        },
+    )
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Event {
-    #[serde(rename = "evt")]
+    #[serde(rename = "cmd", default, deserialize_with = "deserialize_nullable")]
+    pub command: String,
+    #[serde(rename = "evt", default, deserialize_with = "deserialize_nullable")]
     pub event: String,
+    #[serde(default, deserialize_with = "deserialize_nonce")]
+    pub nonce: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
     pub data: serde_json::Map<String, serde_json::Value>,
 }
+
+#[derive(Deserialize)]
+struct Nonce {
+    #[serde(default, deserialize_with = "deserialize_nonce")]
+    nonce: Option<i32>,
+}
+
+/// The nonce of a serialized command, to resolve its receipt when it's dropped unsent.
+pub fn nonce_of(message: &[u8]) -> Option<i32> {
+    serde_json::from_slice::<Nonce>(message).ok()?.nonce
+}
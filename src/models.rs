@@ -0,0 +1,383 @@
+use super::{Snowflake, User};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+macro_rules! impl_try_from_value {
+    ($($ty: ty),*) => {
+        $(
+            impl TryFrom<serde_json::Value> for $ty {
+                type Error = serde_json::Error;
+
+                fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+                    serde_json::from_value(value)
+                }
+            }
+        )*
+    };
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Guild {
+    pub id: Snowflake,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub icon_url: Option<String>,
+    #[serde(default)]
+    pub members: Vec<User>,
+}
+
+/// The payload of `GUILD_STATUS` events.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GuildStatus {
+    pub guild: Guild,
+    /// Deprecated by Discord and always `0`.
+    #[serde(default)]
+    pub online: u32,
+}
+
+/// The response to `GET_GUILDS`; only each guild's ID, name and icon are filled in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuildList {
+    pub guilds: Vec<Guild>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum ChannelType {
+    GuildText,
+    Dm,
+    GuildVoice,
+    GroupDm,
+    GuildCategory,
+    GuildNews,
+    GuildStore,
+    Unknown(u8),
+}
+
+impl From<u8> for ChannelType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => ChannelType::GuildText,
+            1 => ChannelType::Dm,
+            2 => ChannelType::GuildVoice,
+            3 => ChannelType::GroupDm,
+            4 => ChannelType::GuildCategory,
+            5 => ChannelType::GuildNews,
+            6 => ChannelType::GuildStore,
+            _ => ChannelType::Unknown(value),
+        }
+    }
+}
+
+impl From<ChannelType> for u8 {
+    fn from(value: ChannelType) -> Self {
+        match value {
+            ChannelType::GuildText => 0,
+            ChannelType::Dm => 1,
+            ChannelType::GuildVoice => 2,
+            ChannelType::GroupDm => 3,
+            ChannelType::GuildCategory => 4,
+            ChannelType::GuildNews => 5,
+            ChannelType::GuildStore => 6,
+            ChannelType::Unknown(value) => value,
+        }
+    }
+}
+
+impl ChannelType {
+    pub fn is_voice(self) -> bool {
+        matches!(self, ChannelType::GuildVoice)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Channel {
+    pub id: Snowflake,
+    #[serde(default)]
+    pub guild_id: Option<Snowflake>,
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ChannelType,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    #[serde(default)]
+    pub user_limit: Option<u32>,
+    #[serde(default)]
+    pub position: Option<i32>,
+    #[serde(default)]
+    pub voice_states: Vec<VoiceState>,
+    #[serde(default)]
+    pub messages: Vec<Message>,
+}
+
+/// The response to `GET_CHANNELS`; only each channel's ID, name and type are filled in.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChannelList {
+    pub channels: Vec<Channel>,
+}
+
+/// The response to `GET_SELECTED_VOICE_CHANNEL`, `None` while the user isn't in a voice channel.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct SelectedVoiceChannel(pub Option<Channel>);
+
+impl<'de> Deserialize<'de> for SelectedVoiceChannel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Discord answers with `null`, which arrives as an empty object
+        match Option::<serde_json::Map<String, serde_json::Value>>::deserialize(deserializer)? {
+            Some(channel) if !channel.is_empty() => {
+                serde_json::from_value(serde_json::Value::Object(channel))
+                    .map(|channel| SelectedVoiceChannel(Some(channel)))
+                    .map_err(de::Error::custom)
+            }
+            _ => Ok(SelectedVoiceChannel(None)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Message {
+    pub id: Snowflake,
+    #[serde(default)]
+    pub channel_id: Option<Snowflake>,
+    #[serde(default)]
+    pub author: Option<User>,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub edited_timestamp: Option<String>,
+    #[serde(default)]
+    pub tts: bool,
+    #[serde(default)]
+    pub mention_everyone: bool,
+    #[serde(default)]
+    pub mentions: Vec<User>,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VoiceStateFlags {
+    pub mute: bool,
+    pub deaf: bool,
+    pub self_mute: bool,
+    pub self_deaf: bool,
+    pub suppress: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Pan {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Default for Pan {
+    fn default() -> Self {
+        Pan {
+            left: 1.0,
+            right: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VoiceState {
+    #[serde(default)]
+    pub voice_state: VoiceStateFlags,
+    pub user: User,
+    #[serde(default)]
+    pub nick: Option<String>,
+    #[serde(default)]
+    pub volume: f32,
+    #[serde(default)]
+    pub mute: bool,
+    #[serde(default)]
+    pub pan: Pan,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum RelationshipType {
+    None,
+    Friend,
+    Blocked,
+    PendingIncoming,
+    PendingOutgoing,
+    Implicit,
+    Unknown(u8),
+}
+
+impl From<u8> for RelationshipType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => RelationshipType::None,
+            1 => RelationshipType::Friend,
+            2 => RelationshipType::Blocked,
+            3 => RelationshipType::PendingIncoming,
+            4 => RelationshipType::PendingOutgoing,
+            5 => RelationshipType::Implicit,
+            _ => RelationshipType::Unknown(value),
+        }
+    }
+}
+
+impl From<RelationshipType> for u8 {
+    fn from(value: RelationshipType) -> Self {
+        match value {
+            RelationshipType::None => 0,
+            RelationshipType::Friend => 1,
+            RelationshipType::Blocked => 2,
+            RelationshipType::PendingIncoming => 3,
+            RelationshipType::PendingOutgoing => 4,
+            RelationshipType::Implicit => 5,
+            RelationshipType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RelationshipPresence {
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub activity: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Relationship {
+    #[serde(rename = "type")]
+    pub kind: RelationshipType,
+    pub user: User,
+    #[serde(default)]
+    pub presence: RelationshipPresence,
+}
+
+impl Relationship {
+    pub fn is_friend(&self) -> bool {
+        self.kind == RelationshipType::Friend
+    }
+}
+
+/// The response to `GET_RELATIONSHIPS`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RelationshipList {
+    pub relationships: Vec<Relationship>,
+}
+
+impl RelationshipList {
+    pub fn friends(&self) -> impl Iterator<Item = &Relationship> {
+        self.relationships
+            .iter()
+            .filter(|relationship| relationship.is_friend())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum KeyType {
+    KeyboardKey,
+    MouseButton,
+    KeyboardModifierKey,
+    GamepadButton,
+    Unknown(u8),
+}
+
+impl From<u8> for KeyType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => KeyType::KeyboardKey,
+            1 => KeyType::MouseButton,
+            2 => KeyType::KeyboardModifierKey,
+            3 => KeyType::GamepadButton,
+            _ => KeyType::Unknown(value),
+        }
+    }
+}
+
+impl From<KeyType> for u8 {
+    fn from(value: KeyType) -> Self {
+        match value {
+            KeyType::KeyboardKey => 0,
+            KeyType::MouseButton => 1,
+            KeyType::KeyboardModifierKey => 2,
+            KeyType::GamepadButton => 3,
+            KeyType::Unknown(value) => value,
+        }
+    }
+}
+
+/// A key in a shortcut captured through `CAPTURE_SHORTCUT`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ShortcutKey {
+    #[serde(rename = "type")]
+    pub kind: KeyType,
+    pub code: u32,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceType {
+    AudioInput,
+    AudioOutput,
+    VideoInput,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceVendor {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceModel {
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Device {
+    #[serde(rename = "type")]
+    pub kind: DeviceType,
+    pub id: String,
+    #[serde(default)]
+    pub vendor: DeviceVendor,
+    #[serde(default)]
+    pub model: DeviceModel,
+    #[serde(default)]
+    pub related: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub echo_cancellation: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_suppression: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automatic_gain_control: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardware_mute: Option<bool>,
+}
+
+impl_try_from_value!(
+    Guild,
+    GuildStatus,
+    GuildList,
+    Channel,
+    ChannelList,
+    SelectedVoiceChannel,
+    Message,
+    VoiceState,
+    Relationship,
+    RelationshipList,
+    Device
+);
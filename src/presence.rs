@@ -1,32 +1,189 @@
-use serde::{Deserialize, Serialize, Serializer};
-use std::time::SystemTime;
-
-fn serialize_timestamp<S: Serializer>(
-    value: &Option<SystemTime>,
-    serializer: S,
-) -> Result<S::Ok, S::Error> {
-    if let Some(timestamp) = value.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok()) {
-        serializer.serialize_u64(timestamp.as_secs())
-    } else {
-        serializer.serialize_none()
+use super::Snowflake;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::BTreeSet,
+    ops::{BitAnd, BitOr, BitOrAssign},
+    time::{Duration, Instant, SystemTime},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timestamp {
+    System(SystemTime),
+    Monotonic(Instant),
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Timestamp::Monotonic(Instant::now())
+    }
+
+    pub fn to_system_time(self) -> SystemTime {
+        match self {
+            Timestamp::System(time) => time,
+            Timestamp::Monotonic(instant) => {
+                let now = Instant::now();
+                if instant <= now {
+                    SystemTime::now() - (now - instant)
+                } else {
+                    SystemTime::now() + (instant - now)
+                }
+            }
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Serialize)]
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        Timestamp::System(time)
+    }
+}
+
+impl From<Instant> for Timestamp {
+    fn from(instant: Instant) -> Self {
+        Timestamp::Monotonic(instant)
+    }
+}
+
+pub mod limits {
+    pub const TEXT_MIN: usize = 2;
+    pub const TEXT: usize = 128;
+    pub const ASSET_KEY: usize = 32;
+    pub const PARTY_ID: usize = 128;
+    pub const SECRET: usize = 128;
+    pub const BUTTONS: usize = 2;
+    pub const BUTTON_LABEL: usize = 32;
+    pub const BUTTON_URL: usize = 512;
+    pub const FRAME: usize = 64 * 1024;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampPrecision {
+    #[default]
+    Seconds,
+    /// Sends Unix timestamps in milliseconds, which Discord also accepts, for smoother progress
+    /// bars.
+    Milliseconds,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Timestamps {
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        serialize_with = "serialize_timestamp"
-    )]
-    pub start: Option<SystemTime>,
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        serialize_with = "serialize_timestamp"
-    )]
-    pub end: Option<SystemTime>,
+    pub start: Option<Timestamp>,
+    pub end: Option<Timestamp>,
+    pub precision: TimestampPrecision,
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
+impl Serialize for Timestamps {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let unix_time = |timestamp: Option<Timestamp>| {
+            let time = timestamp?
+                .to_system_time()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()?;
+            Some(match self.precision {
+                TimestampPrecision::Seconds => time.as_secs(),
+                TimestampPrecision::Milliseconds => time.as_millis() as u64,
+            })
+        };
+
+        let mut obj = serializer.serialize_map(None)?;
+        if let Some(start) = unix_time(self.start) {
+            obj.serialize_entry("start", &start)?;
+        }
+        if let Some(end) = unix_time(self.end) {
+            obj.serialize_entry("end", &end)?;
+        }
+        obj.end()
+    }
+}
+
+/// Above this, Unix timestamps are taken to be in milliseconds, as in seconds it would be over three
+/// thousand years from now.
+const MAX_SECONDS_TIMESTAMP: u64 = 100_000_000_000;
+
+impl<'de> Deserialize<'de> for Timestamps {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            #[serde(default)]
+            start: Option<u64>,
+            #[serde(default)]
+            end: Option<u64>,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let precision = if raw
+            .start
+            .into_iter()
+            .chain(raw.end)
+            .any(|time| time >= MAX_SECONDS_TIMESTAMP)
+        {
+            TimestampPrecision::Milliseconds
+        } else {
+            TimestampPrecision::Seconds
+        };
+        let timestamp = |time: Option<u64>| {
+            time.map(|time| {
+                Timestamp::System(
+                    SystemTime::UNIX_EPOCH
+                        + match precision {
+                            TimestampPrecision::Seconds => Duration::from_secs(time),
+                            TimestampPrecision::Milliseconds => Duration::from_millis(time),
+                        },
+                )
+            })
+        };
+        Ok(Timestamps {
+            start: timestamp(raw.start),
+            end: timestamp(raw.end),
+            precision,
+        })
+    }
+}
+
+impl Timestamps {
+    pub fn elapsed_since(start: impl Into<Timestamp>) -> Self {
+        Timestamps {
+            start: Some(start.into()),
+            end: None,
+            precision: TimestampPrecision::Seconds,
+        }
+    }
+
+    /// Shows a countdown ending `remaining` from now.
+    pub fn remaining(remaining: Duration) -> Self {
+        Timestamps {
+            start: None,
+            end: Some(Timestamp::Monotonic(Instant::now() + remaining)),
+            precision: TimestampPrecision::Seconds,
+        }
+    }
+
+    /// Shows a progress bar from `start` to `end`, e.g. for the current song.
+    pub fn between(start: impl Into<Timestamp>, end: impl Into<Timestamp>) -> Self {
+        Timestamps {
+            start: Some(start.into()),
+            end: Some(end.into()),
+            precision: TimestampPrecision::Seconds,
+        }
+    }
+
+    pub fn with_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn elapsed(elapsed: Duration) -> Self {
+        let start = match Instant::now().checked_sub(elapsed) {
+            Some(instant) => Timestamp::Monotonic(instant),
+            None => Timestamp::System(SystemTime::now() - elapsed),
+        };
+        Self::elapsed_since(start)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Images {
     #[serde(rename = "large_image", skip_serializing_if = "Option::is_none")]
     pub large_key: Option<String>,
@@ -38,31 +195,123 @@ pub struct Images {
     pub small_text: Option<String>,
 }
 
-fn private(value: &bool) -> bool {
-    !*value
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum PartyPrivacy {
+    #[default]
+    Private,
+    Public,
 }
 
-fn serialize_public<S: Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
-    serializer.serialize_u8(*value as u8)
+impl From<PartyPrivacy> for u8 {
+    fn from(value: PartyPrivacy) -> Self {
+        match value {
+            PartyPrivacy::Private => 0,
+            PartyPrivacy::Public => 1,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
+impl TryFrom<u8> for PartyPrivacy {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PartyPrivacy::Private),
+            1 => Ok(PartyPrivacy::Public),
+            _ => Err(format!("unknown party privacy: {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "RawParty")]
 pub struct Party {
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub size: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub max: Option<u32>,
-    #[serde(
-        skip_serializing_if = "private",
-        rename = "privacy",
-        serialize_with = "serialize_public"
-    )]
-    pub public: bool,
+    /// Left out of the activity when `None`, which Discord treats as private.
+    pub privacy: Option<PartyPrivacy>,
+}
+
+impl Serialize for Party {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut obj = serializer.serialize_map(None)?;
+        if let Some(id) = &self.id {
+            obj.serialize_entry("id", id)?;
+        }
+        // Discord only takes both halves together; a lone one is reported by `validate_presence`
+        if let (Some(size), Some(max)) = (self.size, self.max) {
+            obj.serialize_entry("size", &[size, max])?;
+        }
+        if let Some(privacy) = self.privacy {
+            obj.serialize_entry("privacy", &privacy)?;
+        }
+        obj.end()
+    }
+}
+
+/// Discord sends and reports the party's size as a `[size, max]` pair; separate `size` and `max`
+/// fields are accepted too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PartySize {
+    Single(u32),
+    Pair(u32, u32),
+}
+
+#[derive(Deserialize)]
+struct RawParty {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    size: Option<PartySize>,
+    #[serde(default)]
+    max: Option<u32>,
+    #[serde(default)]
+    privacy: Option<PartyPrivacy>,
+}
+
+impl From<RawParty> for Party {
+    fn from(raw: RawParty) -> Self {
+        let (size, max) = match raw.size {
+            Some(PartySize::Pair(size, max)) => (Some(size), Some(max)),
+            Some(PartySize::Single(size)) => (Some(size), raw.max),
+            None => (None, raw.max),
+        };
+        Party {
+            id: raw.id,
+            size,
+            max,
+            privacy: raw.privacy,
+        }
+    }
+}
+
+impl Party {
+    pub fn of(id: impl Into<String>) -> Self {
+        Party {
+            id: Some(id.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the current and maximum size; a size over the maximum is reported by
+    /// [`validate_presence`](crate::validate_presence).
+    pub fn size(mut self, size: u32, max: u32) -> Self {
+        self.size = Some(size);
+        self.max = Some(max);
+        self
+    }
+
+    pub fn privacy(mut self, privacy: PartyPrivacy) -> Self {
+        self.privacy = Some(privacy);
+        self
+    }
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Secrets {
     #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
     pub match_: Option<String>,
@@ -72,26 +321,399 @@ pub struct Secrets {
     pub spectate: Option<String>,
 }
 
-#[derive(Debug, Default, Serialize)]
+const SECRET_LEN: usize = 32;
+
+impl Secrets {
+    /// Random match, join and spectate secrets.
+    pub fn generate() -> Self {
+        Secrets {
+            match_: Some(Self::random_secret()),
+            join: Some(Self::random_secret()),
+            spectate: Some(Self::random_secret()),
+        }
+    }
+
+    /// A random, URL-safe secret drawn from a cryptographically secure generator.
+    pub fn random_secret() -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect()
+    }
+
+    /// A random secret carrying `data` (e.g. a lobby ID) that can be read back with
+    /// [`data`](Self::data) when the secret is received. The data isn't hidden from the users who
+    /// receive the secret, and needs to fit in [`limits::SECRET`] along with the random part.
+    pub fn random_with_data(data: &str) -> String {
+        format!("{}.{}", Self::random_secret(), data)
+    }
+
+    /// The data embedded in a secret made by [`random_with_data`](Self::random_with_data).
+    pub fn data(secret: &str) -> Option<&str> {
+        secret.split_once('.').map(|(_, data)| data)
+    }
+}
+
+/// Also deserializes from the bare labels Discord reports buttons as, leaving their URLs empty.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Button {
+    pub label: String,
+    pub url: String,
+}
+
+impl<'de> Deserialize<'de> for Button {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Label(String),
+            Full { label: String, url: String },
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Label(label) => Button {
+                label,
+                url: String::new(),
+            },
+            Raw::Full { label, url } => Button { label, url },
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Desktop,
+    Xbox,
+    Samsung,
+    Ios,
+    Android,
+    Embedded,
+    Ps4,
+    Ps5,
+}
+
+/// The bitfield describing what an activity supports, combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ActivityFlags(u32);
+
+impl ActivityFlags {
+    pub const INSTANCE: Self = ActivityFlags(1 << 0);
+    pub const JOIN: Self = ActivityFlags(1 << 1);
+    pub const SPECTATE: Self = ActivityFlags(1 << 2);
+    pub const JOIN_REQUEST: Self = ActivityFlags(1 << 3);
+    pub const SYNC: Self = ActivityFlags(1 << 4);
+    pub const PLAY: Self = ActivityFlags(1 << 5);
+    pub const PARTY_PRIVACY_FRIENDS: Self = ActivityFlags(1 << 6);
+    pub const PARTY_PRIVACY_VOICE_CHANNEL: Self = ActivityFlags(1 << 7);
+    pub const EMBEDDED: Self = ActivityFlags(1 << 8);
+
+    pub const fn empty() -> Self {
+        ActivityFlags(0)
+    }
+
+    /// Keeps bits this crate has no constant for, so newer flags can still be sent.
+    pub const fn from_bits(bits: u32) -> Self {
+        ActivityFlags(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for ActivityFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        ActivityFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ActivityFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for ActivityFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        ActivityFlags(self.0 & rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum ActivityType {
+    #[default]
+    Playing,
+    Listening,
+    Watching,
+    Competing,
+}
+
+impl From<ActivityType> for u8 {
+    fn from(value: ActivityType) -> Self {
+        match value {
+            ActivityType::Playing => 0,
+            ActivityType::Listening => 2,
+            ActivityType::Watching => 3,
+            ActivityType::Competing => 5,
+        }
+    }
+}
+
+impl TryFrom<u8> for ActivityType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ActivityType::Playing),
+            2 => Ok(ActivityType::Listening),
+            3 => Ok(ActivityType::Watching),
+            5 => Ok(ActivityType::Competing),
+            _ => Err(format!("unsupported activity type: {}", value)),
+        }
+    }
+}
+
+/// Also deserializes from the activity Discord reports back, whose fields it doesn't know of are
+/// ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Presence {
+    #[serde(rename = "type")]
+    pub kind: ActivityType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub state: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamps: Option<Timestamps>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        rename = "assets",
+        alias = "images",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub images: Option<Images>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub party: Option<Party>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secrets: Option<Secrets>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<Button>,
+    /// Whether the activity is a specific instance of the game, such as a match, rather than the
+    /// game as a whole.
+    #[serde(skip_serializing_if = "is_false")]
+    pub instance: bool,
+    #[serde(skip_serializing_if = "ActivityFlags::is_empty")]
+    pub flags: ActivityFlags,
+    /// The platforms the activity can be joined from; Discord assumes desktop only when empty.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub supported_platforms: BTreeSet<Platform>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum PremiumType {
+    #[default]
+    None,
+    NitroClassic,
+    Nitro,
+    NitroBasic,
+    Unknown(u8),
+}
+
+impl From<u8> for PremiumType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => PremiumType::None,
+            1 => PremiumType::NitroClassic,
+            2 => PremiumType::Nitro,
+            3 => PremiumType::NitroBasic,
+            _ => PremiumType::Unknown(value),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl From<PremiumType> for u8 {
+    fn from(value: PremiumType) -> Self {
+        match value {
+            PremiumType::None => 0,
+            PremiumType::NitroClassic => 1,
+            PremiumType::Nitro => 2,
+            PremiumType::NitroBasic => 3,
+            PremiumType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User {
-    pub id: String,
+    pub id: Snowflake,
     pub username: String,
+    /// `"0"` for users who migrated to unique usernames.
+    #[serde(default)]
     pub discriminator: String,
+    #[serde(default)]
+    pub global_name: Option<String>,
+    #[serde(default)]
     pub avatar: Option<String>,
+    #[serde(default)]
+    pub avatar_decoration: Option<String>,
+    #[serde(default)]
+    pub bot: bool,
+    #[serde(default)]
+    pub flags: u64,
+    #[serde(default, deserialize_with = "deserialize_premium_type")]
+    pub premium_type: PremiumType,
+}
+
+fn deserialize_premium_type<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<PremiumType, D::Error> {
+    Ok(Option::<PremiumType>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    WebP,
+    /// Only available for animated avatars, falling back to PNG for the others.
+    Gif,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Gif => "gif",
+        }
+    }
+}
+
+const CDN_URL: &str = "https://cdn.discordapp.com";
+
+/// Rounds `size` to the nearest avatar size the CDN serves, rounding ties up.
+fn avatar_size(size: u16) -> u16 {
+    let size = size.clamp(16, 4096);
+    let upper = size.next_power_of_two();
+    let lower = upper >> u32::from(upper != size);
+    if size - lower < upper - size {
+        lower
+    } else {
+        upper
+    }
+}
+
+impl User {
+    /// The URL of the user's avatar, or of their default avatar (always a PNG of a fixed size) if
+    /// they haven't set one. The CDN only serves powers of two between 16 and 4096, so `size` is
+    /// rounded to the nearest of those.
+    pub fn avatar_url(&self, size: u16, format: ImageFormat) -> String {
+        let size = avatar_size(size);
+        match &self.avatar {
+            Some(hash) => {
+                let format = if format == ImageFormat::Gif && !hash.starts_with("a_") {
+                    ImageFormat::Png
+                } else {
+                    format
+                };
+                format!(
+                    "{}/avatars/{}/{}.{}?size={}",
+                    CDN_URL,
+                    self.id,
+                    hash,
+                    format.extension(),
+                    size
+                )
+            }
+            None => {
+                let index = match self.discriminator.parse::<u16>() {
+                    Ok(discriminator) if discriminator != 0 => u64::from(discriminator % 5),
+                    _ => (self.id.get() >> 22) % 6,
+                };
+                format!("{}/embed/avatars/{}.png", CDN_URL, index)
+            }
+        }
+    }
+
+    /// The name Discord shows for the user: their display name if they set one, otherwise their
+    /// username, with the discriminator for users who haven't migrated to unique usernames.
+    pub fn display_name(&self) -> String {
+        match &self.global_name {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ if self.discriminator.is_empty() || self.discriminator == "0" => {
+                self.username.clone()
+            }
+            _ => format!("{}#{}", self.username, self.discriminator),
+        }
+    }
+}
+
+pub trait ToPresence {
+    fn to_presence(&self) -> Presence;
+}
+
+impl ToPresence for Presence {
+    fn to_presence(&self) -> Presence {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rounds_avatar_sizes() {
+        let user = serde_json::from_value::<User>(json!({
+            "id": "42",
+            "username": "player",
+            "avatar": "hash",
+        }))
+        .unwrap();
+        for (size, expected) in [
+            (0, 16),
+            (64, 64),
+            (95, 64),
+            (96, 128),
+            (1000, 1024),
+            (u16::MAX, 4096),
+        ] {
+            assert_eq!(
+                user.avatar_url(size, ImageFormat::Png),
+                format!("{}/avatars/42/hash.png?size={}", CDN_URL, expected)
+            );
+        }
+    }
 }
@@ -0,0 +1,59 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Allows at most `max_updates` presence updates within any `window`; updates past the limit are
+/// held back and coalesced so that only the latest one is sent once the window allows it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_updates: usize,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    pub fn new(max_updates: usize, window: Duration) -> Self {
+        RateLimit {
+            max_updates,
+            window,
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit::new(5, Duration::from_secs(20))
+    }
+}
+
+pub struct RateLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        RateLimiter {
+            sent: VecDeque::new(),
+        }
+    }
+
+    pub fn try_acquire(&mut self, limit: Option<RateLimit>) -> bool {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return true,
+        };
+        let now = Instant::now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|&sent_at| now.duration_since(sent_at) >= limit.window)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= limit.max_updates {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
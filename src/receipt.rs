@@ -0,0 +1,86 @@
+use super::{Error, ErrorCode, Subscription};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::{marker::PhantomData, time::Duration};
+
+pub type CommandResult<T = Map<String, Value>> = Result<T, Error>;
+
+pub(crate) enum PendingResponse {
+    /// A subscription change, along with whether it subscribes or unsubscribes.
+    Subscription(Subscription, bool),
+    Command(Sender<CommandResult>),
+}
+
+/// Resolves with Discord's response to the command it was returned for, or with a
+/// `NO_RESPONSE` error if the command was superseded or the connection dropped first.
+#[derive(Debug)]
+pub struct Receipt<T = Map<String, Value>> {
+    rx: Receiver<CommandResult>,
+    result: Option<CommandResult<T>>,
+    _data: PhantomData<fn() -> T>,
+}
+
+/// The receipt for a command sent through [`Rpc::send_command`](crate::Rpc::send_command),
+/// resolving with the raw `data` of the response.
+pub type CommandHandle = Receipt<Value>;
+
+fn no_response() -> Error {
+    Error {
+        message: "No response received".to_string(),
+        code: ErrorCode::NoResponse,
+    }
+}
+
+fn parse<T: DeserializeOwned>(result: CommandResult) -> CommandResult<T> {
+    serde_json::from_value(Value::Object(result?)).map_err(|err| Error {
+        message: err.to_string(),
+        code: ErrorCode::ReadCorrupt,
+    })
+}
+
+impl<T: DeserializeOwned + Clone> Receipt<T> {
+    pub(crate) fn new() -> (Sender<CommandResult>, Self) {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        (
+            tx,
+            Receipt {
+                rx,
+                result: None,
+                _data: PhantomData,
+            },
+        )
+    }
+
+    pub(crate) fn resolved(result: CommandResult) -> Self {
+        let (tx, receipt) = Self::new();
+        let _ = tx.send(result);
+        receipt
+    }
+
+    pub fn try_get(&mut self) -> Option<CommandResult<T>> {
+        if self.result.is_none() {
+            self.result = match self.rx.try_recv() {
+                Ok(result) => Some(parse(result)),
+                Err(TryRecvError::Disconnected) => Some(Err(no_response())),
+                Err(TryRecvError::Empty) => None,
+            };
+        }
+        self.result.clone()
+    }
+
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Option<CommandResult<T>> {
+        if self.result.is_none() {
+            self.result = match self.rx.recv_timeout(timeout) {
+                Ok(result) => Some(parse(result)),
+                Err(RecvTimeoutError::Disconnected) => Some(Err(no_response())),
+                Err(RecvTimeoutError::Timeout) => None,
+            };
+        }
+        self.result.clone()
+    }
+
+    pub fn is_resolved(&mut self) -> bool {
+        self.try_get().is_some()
+    }
+}
@@ -2,3 +2,11 @@
 mod macos;
 #[cfg(target_os = "macos")]
 pub use macos::*;
+#[cfg(target_family = "windows")]
+mod windows;
+#[cfg(target_family = "windows")]
+pub use windows::*;
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+pub use linux::*;
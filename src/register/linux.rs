@@ -0,0 +1,63 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn applications_dir() -> Result<PathBuf, String> {
+    let data_home = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var_os("HOME")
+                .filter(|dir| !dir.is_empty())
+                .ok_or_else(|| "Could not determine home directory".to_string())?;
+            PathBuf::from(home).join(".local/share")
+        }
+    };
+    Ok(data_home.join("applications"))
+}
+
+pub(crate) fn register_command(app_id: &str, command: &str) -> Result<(), String> {
+    let mime_type = format!("x-scheme-handler/discord-{}", app_id);
+    let file_name = format!("discord-{}.desktop", app_id);
+    let dir = applications_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+    let path = dir.join(&file_name);
+    fs::write(
+        &path,
+        format!(
+            "[Desktop Entry]\nName=Game {}\nExec={} %u\nType=Application\nNoDisplay=true\n\
+             Categories=Discord;Games;\nMimeType={};\n",
+            app_id, command, mime_type
+        ),
+    )
+    .map_err(|err| format!("Could not write {}: {}", path.display(), err))?;
+
+    let status = Command::new("xdg-mime")
+        .args(["default", &file_name, &mime_type])
+        .status()
+        .map_err(|err| format!("Could not run xdg-mime: {}", err))?;
+    if !status.success() {
+        return Err(format!("Error in xdg-mime: {}", status));
+    }
+    // Not every desktop environment ships this, and xdg-mime already recorded the association
+    let _ = Command::new("update-desktop-database").arg(&dir).status();
+    Ok(())
+}
+
+pub fn register_url(app_id: &str) -> Result<(), String> {
+    let exe_path = env::current_exe()
+        .map_err(|err| format!("Could not determine executable path: {}", err))?;
+    let exe_path = exe_path.to_string_lossy();
+    // Desktop entry Exec keys need quoting (and backslash-escaping) for paths with spaces
+    let exe_path = format!(
+        "\"{}\"",
+        exe_path
+            .replace('\\', "\\\\\\\\")
+            .replace('"', "\\\\\"")
+            .replace('`', "\\\\`")
+            .replace('$', "\\\\$")
+    );
+    register_command(app_id, &exe_path)
+}
+
+pub fn register_steam_game(app_id: &str, steam_id: &str) -> Result<(), String> {
+    register_command(app_id, &format!("xdg-open steam://rungameid/{}", steam_id))
+}
@@ -4,6 +4,8 @@ use core_foundation::{
     string::{CFString, CFStringRef},
     url::CFURLRef,
 };
+use serde_json::json;
+use std::{env, fs, path::PathBuf};
 
 #[link(name = "CoreServices", kind = "framework")]
 extern "C" {
@@ -40,3 +42,19 @@ pub fn register_url(app_id: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+pub(crate) fn register_command(app_id: &str, command: &str) -> Result<(), String> {
+    let home = env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .ok_or_else(|| "Could not determine home directory".to_string())?;
+    let dir = PathBuf::from(home).join("Library/Application Support/discord/games");
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+    let path = dir.join(format!("{}.json", app_id));
+    fs::write(&path, json!({ "command": command }).to_string())
+        .map_err(|err| format!("Could not write {}: {}", path.display(), err))
+}
+
+pub fn register_steam_game(app_id: &str, steam_id: &str) -> Result<(), String> {
+    register_command(app_id, &format!("steam://rungameid/{}", steam_id))
+}
@@ -0,0 +1,94 @@
+use std::{env, ffi::OsStr, iter, os::windows::ffi::OsStrExt, ptr};
+use winapi::{
+    shared::{minwindef::DWORD, winerror::ERROR_SUCCESS},
+    um::{
+        winnt::REG_SZ,
+        winreg::{RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, RRF_RT_REG_SZ},
+    },
+};
+
+fn to_wide(value: impl AsRef<OsStr>) -> Vec<u16> {
+    value.as_ref().encode_wide().chain(iter::once(0)).collect()
+}
+
+fn set_value(key: &str, name: Option<&str>, value: &str) -> Result<(), String> {
+    let key_wide = to_wide(key);
+    let name_wide = name.map(to_wide);
+    let value_wide = to_wide(value);
+    let status = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            key_wide.as_ptr(),
+            name_wide.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+            REG_SZ,
+            value_wide.as_ptr() as *const _,
+            (value_wide.len() * 2) as DWORD,
+        )
+    };
+    if status != ERROR_SUCCESS as i32 {
+        return Err(format!("Error in RegSetKeyValueW for {}: {}", key, status));
+    }
+    Ok(())
+}
+
+fn steam_exe_path() -> Result<String, String> {
+    let key_wide = to_wide(r"Software\Valve\Steam");
+    let name_wide = to_wide("SteamExe");
+    let mut buffer = [0_u16; 260];
+    let mut size = (buffer.len() * 2) as DWORD;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key_wide.as_ptr(),
+            name_wide.as_ptr(),
+            RRF_RT_REG_SZ,
+            ptr::null_mut(),
+            buffer.as_mut_ptr() as *mut _,
+            &mut size,
+        )
+    };
+    if status != ERROR_SUCCESS as i32 {
+        return Err(format!(
+            "Could not determine Steam executable path: {}",
+            status
+        ));
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    // Steam stores the path with forward slashes
+    Ok(String::from_utf16_lossy(&buffer[..len]).replace('/', "\\"))
+}
+
+pub(crate) fn register_command(app_id: &str, command: &str) -> Result<(), String> {
+    let exe_path = env::current_exe()
+        .map_err(|err| format!("Could not determine executable path: {}", err))?;
+    let exe_path = exe_path.to_string_lossy();
+    let protocol_key = format!(r"Software\Classes\discord-{}", app_id);
+    set_value(
+        &protocol_key,
+        None,
+        &format!("URL:Run game {} protocol", app_id),
+    )?;
+    set_value(&protocol_key, Some("URL Protocol"), "")?;
+    set_value(&format!(r"{}\DefaultIcon", protocol_key), None, &exe_path)?;
+    set_value(
+        &format!(r"{}\shell\open\command", protocol_key),
+        None,
+        command,
+    )
+}
+
+pub fn register_url(app_id: &str) -> Result<(), String> {
+    let exe_path = env::current_exe()
+        .map_err(|err| format!("Could not determine executable path: {}", err))?;
+    register_command(
+        app_id,
+        &format!("\"{}\" \"%1\"", exe_path.to_string_lossy()),
+    )
+}
+
+pub fn register_steam_game(app_id: &str, steam_id: &str) -> Result<(), String> {
+    register_command(
+        app_id,
+        &format!("\"{}\" steam://rungameid/{}", steam_exe_path()?, steam_id),
+    )
+}
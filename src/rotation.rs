@@ -0,0 +1,40 @@
+use super::Secrets;
+use std::time::{Duration, Instant};
+
+pub struct SecretRotation {
+    pub interval: Duration,
+    pub next_time: Instant,
+}
+
+impl SecretRotation {
+    pub fn new(interval: Duration) -> Self {
+        SecretRotation {
+            interval,
+            next_time: Instant::now() + interval,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.next_time = Instant::now() + self.interval;
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_time
+    }
+}
+
+pub fn rotate(secrets: &mut Secrets) -> bool {
+    let mut rotated = false;
+    for secret in [&mut secrets.join, &mut secrets.spectate]
+        .into_iter()
+        .flatten()
+    {
+        // Keep whatever data was embedded, only the random part identifies the secret
+        *secret = match Secrets::data(secret) {
+            Some(data) => Secrets::random_with_data(data),
+            None => Secrets::random_secret(),
+        };
+        rotated = true;
+    }
+    rotated
+}
@@ -0,0 +1,101 @@
+use super::{Error, ErrorCode};
+use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+
+/// An error code from the payload of an `ERROR` event, which Discord numbers independently of the
+/// close codes in [`ErrorCode`](crate::ErrorCode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcErrorCode {
+    UnknownError,
+    InvalidPayload,
+    InvalidCommand,
+    InvalidGuild,
+    InvalidEvent,
+    InvalidChannel,
+    InvalidPermissions,
+    InvalidClientId,
+    InvalidOrigin,
+    InvalidToken,
+    InvalidUser,
+    OAuth2Error,
+    SelectChannelTimedOut,
+    GetGuildTimedOut,
+    SelectVoiceForceRequired,
+    CaptureShortcutAlreadyListening,
+}
+
+impl RpcErrorCode {
+    pub fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            1000 => RpcErrorCode::UnknownError,
+            4000 => RpcErrorCode::InvalidPayload,
+            4002 => RpcErrorCode::InvalidCommand,
+            4003 => RpcErrorCode::InvalidGuild,
+            4004 => RpcErrorCode::InvalidEvent,
+            4005 => RpcErrorCode::InvalidChannel,
+            4006 => RpcErrorCode::InvalidPermissions,
+            4007 => RpcErrorCode::InvalidClientId,
+            4008 => RpcErrorCode::InvalidOrigin,
+            4009 => RpcErrorCode::InvalidToken,
+            4010 => RpcErrorCode::InvalidUser,
+            5000 => RpcErrorCode::OAuth2Error,
+            5001 => RpcErrorCode::SelectChannelTimedOut,
+            5002 => RpcErrorCode::GetGuildTimedOut,
+            5003 => RpcErrorCode::SelectVoiceForceRequired,
+            5004 => RpcErrorCode::CaptureShortcutAlreadyListening,
+            _ => return None,
+        })
+    }
+
+    pub fn code(self) -> u32 {
+        match self {
+            RpcErrorCode::UnknownError => 1000,
+            RpcErrorCode::InvalidPayload => 4000,
+            RpcErrorCode::InvalidCommand => 4002,
+            RpcErrorCode::InvalidGuild => 4003,
+            RpcErrorCode::InvalidEvent => 4004,
+            RpcErrorCode::InvalidChannel => 4005,
+            RpcErrorCode::InvalidPermissions => 4006,
+            RpcErrorCode::InvalidClientId => 4007,
+            RpcErrorCode::InvalidOrigin => 4008,
+            RpcErrorCode::InvalidToken => 4009,
+            RpcErrorCode::InvalidUser => 4010,
+            RpcErrorCode::OAuth2Error => 5000,
+            RpcErrorCode::SelectChannelTimedOut => 5001,
+            RpcErrorCode::GetGuildTimedOut => 5002,
+            RpcErrorCode::SelectVoiceForceRequired => 5003,
+            RpcErrorCode::CaptureShortcutAlreadyListening => 5004,
+        }
+    }
+}
+
+/// An error reported by Discord through an `ERROR` event.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RpcError {
+    pub code: u32,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn known_code(&self) -> Option<RpcErrorCode> {
+        RpcErrorCode::from_code(self.code)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl error::Error for RpcError {}
+
+impl From<RpcError> for Error {
+    fn from(err: RpcError) -> Self {
+        Error {
+            message: err.message,
+            code: ErrorCode::Rpc(err.code),
+        }
+    }
+}
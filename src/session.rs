@@ -0,0 +1,13 @@
+use super::{EventHandlers, Presence};
+
+#[derive(Default)]
+pub struct Session {
+    pub presence: Option<Presence>,
+    pub handlers: EventHandlers,
+}
+
+impl Session {
+    pub fn new(presence: Option<Presence>, handlers: EventHandlers) -> Self {
+        Session { presence, handlers }
+    }
+}
@@ -0,0 +1,45 @@
+use super::{connection::TrafficStats, Error};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub reconnect_count: u64,
+    /// The delay before the next connection attempt if currently disconnected.
+    pub backoff_delay: Duration,
+    pub traffic: TrafficStats,
+    pub last_error: Option<Error>,
+    pub pipe_index: Option<u32>,
+    pub(crate) has_connected: bool,
+}
+
+impl Stats {
+    pub fn since_last_frame(&self) -> Option<Duration> {
+        self.traffic
+            .last_frame_received_at
+            .map(|received_at| received_at.elapsed())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ConnectionStatus {
+    Connected {
+        since: Instant,
+    },
+    Reconnecting {
+        next_attempt: Instant,
+        /// The number of failed attempts since the last successful connection.
+        attempts: u32,
+    },
+    /// The IO thread has stopped or hasn't made its first attempt yet.
+    Disconnected {
+        last_error: Option<Error>,
+    },
+    /// Turned off through [`Rpc::set_enabled`](crate::Rpc::set_enabled) or `DISCORD_RPC_DISABLED`.
+    Disabled,
+}
+
+impl ConnectionStatus {
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionStatus::Connected { .. })
+    }
+}
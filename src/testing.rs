@@ -0,0 +1,834 @@
+use super::{
+    connection::opcode, Connector, DisconnectReason, Error, Event, EventHandlers, IpcConfig,
+    JoinReply, Presence, Receipt, RpcClient, RpcError, RpcErrorCode, Snowflake, Subscription,
+    Transport, User,
+};
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+#[cfg(target_family = "unix")]
+use std::{
+    fs,
+    net::Shutdown,
+    ops::Deref,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[derive(Default)]
+pub struct FakeRpc {
+    pub handlers: EventHandlers,
+    pub presence_updates: Vec<Option<Presence>>,
+    pub join_replies: Vec<(Snowflake, JoinReply)>,
+    /// The error receipts for presence updates resolve with, succeeding when `None`.
+    pub presence_error: Option<Error>,
+    events: VecDeque<Event>,
+}
+
+impl FakeRpc {
+    pub fn new(handlers: EventHandlers) -> Self {
+        FakeRpc {
+            handlers,
+            ..Default::default()
+        }
+    }
+
+    pub fn last_presence(&self) -> Option<&Presence> {
+        self.presence_updates.last().and_then(Option::as_ref)
+    }
+
+    pub fn inject(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    pub fn inject_connect(&mut self, user: Option<User>) {
+        self.events.push_back(Event::Connected(user));
+    }
+
+    pub fn inject_disconnect(&mut self, reason: DisconnectReason) {
+        self.events.push_back(Event::Disconnected(reason));
+    }
+
+    pub fn inject_error(&mut self, error: Error) {
+        self.events.push_back(Event::GotError(error));
+    }
+
+    pub fn inject_rpc_error(&mut self, error: RpcError) {
+        self.events.push_back(Event::GotRpcError(error));
+    }
+
+    pub fn inject_join_game(&mut self, secret: impl Into<String>) {
+        self.events.push_back(Event::GameJoined(secret.into()));
+    }
+
+    pub fn inject_spectate_game(&mut self, secret: impl Into<String>) {
+        self.events
+            .push_back(Event::StartedSpectating(secret.into()));
+    }
+
+    pub fn inject_join_request(&mut self, user: User) {
+        self.events.push_back(Event::JoinRequested(user));
+    }
+
+    fn next_event(&mut self) -> Option<Event> {
+        loop {
+            let event = self.events.pop_front()?;
+            if let Event::JoinRequested(user) = &event {
+                if let Some(reply) = self.handlers.decide_join_request(user) {
+                    self.join_replies.push((user.id, reply));
+                    continue;
+                }
+            }
+            return Some(event);
+        }
+    }
+}
+
+impl RpcClient for FakeRpc {
+    fn update_presence(&mut self, presence: Option<&Presence>) -> Receipt {
+        self.presence_updates.push(presence.cloned());
+        Receipt::resolved(match &self.presence_error {
+            Some(err) => Err(err.clone()),
+            None => Ok(Default::default()),
+        })
+    }
+
+    fn reply_to_join_request(&mut self, user_id: Snowflake, reply: JoinReply) -> Receipt {
+        self.join_replies.push((user_id, reply));
+        Receipt::resolved(Ok(Default::default()))
+    }
+
+    fn modify_handlers(&mut self, f: &mut dyn FnMut(&mut EventHandlers)) {
+        f(&mut self.handlers);
+    }
+
+    fn subscriptions(&self) -> Vec<Subscription> {
+        self.handlers.subscriptions()
+    }
+
+    fn check_events(&mut self) {
+        while let Some(event) = self.next_event() {
+            self.handlers.dispatch(event);
+        }
+    }
+
+    fn try_recv_event(&mut self) -> Option<Event> {
+        self.next_event()
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    available: bool,
+    frozen: bool,
+    user: Option<User>,
+    generation: u64,
+    connected: bool,
+    outgoing: VecDeque<u8>,
+    commands: Vec<Value>,
+    subscriptions: Vec<String>,
+    rejected_events: Vec<String>,
+}
+
+impl MockState {
+    fn queue(&mut self, opcode: u32, message: &Value) {
+        let message = message.to_string().into_bytes();
+        self.outgoing.extend(opcode.to_le_bytes());
+        self.outgoing.extend((message.len() as u32).to_le_bytes());
+        self.outgoing.extend(message);
+    }
+
+    fn dispatch(&mut self, event: &str, data: Value) {
+        self.queue(
+            opcode::FRAME,
+            &json!({ "cmd": "DISPATCH", "evt": event, "nonce": null, "data": data }),
+        );
+    }
+
+    fn receive(&mut self, opcode: u32, message: &[u8]) {
+        if self.frozen {
+            return;
+        }
+        let message = serde_json::from_slice::<Value>(message).unwrap_or(Value::Null);
+        match opcode {
+            opcode::HANDSHAKE => {
+                let user = self.user.clone();
+                let config = json!({
+                    "cdn_host": "cdn.discordapp.com",
+                    "api_endpoint": "//discord.com/api",
+                    "environment": "production",
+                });
+                self.dispatch("READY", json!({ "v": 1, "config": config, "user": user }));
+            }
+            opcode::FRAME => {
+                let command = message["cmd"].as_str().unwrap_or_default().to_string();
+                let event = message["evt"].as_str().map(str::to_string);
+                match (command.as_str(), &event) {
+                    ("SUBSCRIBE", Some(event)) if self.rejected_events.contains(event) => {
+                        self.queue(
+                            opcode::FRAME,
+                            &json!({
+                                "cmd": command,
+                                "evt": "ERROR",
+                                "nonce": message["nonce"],
+                                "data": {
+                                    "code": RpcErrorCode::InvalidPermissions.code(),
+                                    "message": "Not authenticated or invalid scope",
+                                },
+                            }),
+                        );
+                        self.commands.push(message);
+                        return;
+                    }
+                    ("SUBSCRIBE", Some(event)) => self.subscriptions.push(event.clone()),
+                    ("UNSUBSCRIBE", Some(event)) => self.subscriptions.retain(|e| e != event),
+                    _ => {}
+                }
+                self.queue(
+                    opcode::FRAME,
+                    &json!({
+                        "cmd": command,
+                        "evt": event,
+                        "nonce": message["nonce"],
+                        "data": {},
+                    }),
+                );
+                self.commands.push(message);
+            }
+            opcode::CLOSE => self.connected = false,
+            opcode::PING => self.queue(opcode::PONG, &message),
+            _ => {}
+        }
+    }
+}
+
+/// An in-memory stand-in for the Discord client, to drive a real [`Rpc`](crate::Rpc) in tests
+/// through [`Rpc::with_connector`](crate::Rpc::with_connector). It answers the handshake and every command, records what it receives
+/// and can dispatch events on demand.
+#[derive(Clone)]
+pub struct MockDiscord {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockDiscord {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl MockDiscord {
+    pub fn new(user: Option<User>) -> Self {
+        MockDiscord {
+            state: Arc::new(Mutex::new(MockState {
+                available: true,
+                user,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Makes connection attempts fail while `false`, as if Discord wasn't running.
+    pub fn set_available(&self, available: bool) {
+        self.state.lock().available = available;
+    }
+
+    /// Ignores everything received while `true`, like a hung client that keeps the pipe open.
+    pub fn set_frozen(&self, frozen: bool) {
+        self.state.lock().frozen = frozen;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().connected
+    }
+
+    /// Drops the current connection from Discord's side.
+    pub fn disconnect(&self) {
+        let mut state = self.state.lock();
+        state.connected = false;
+        state.outgoing.clear();
+    }
+
+    /// Every command frame received, in order.
+    pub fn commands(&self) -> Vec<Value> {
+        self.state.lock().commands.clone()
+    }
+
+    /// The `activity` argument of every `SET_ACTIVITY` received, `Null` for cleared presences.
+    pub fn activities(&self) -> Vec<Value> {
+        self.state
+            .lock()
+            .commands
+            .iter()
+            .filter(|command| command["cmd"] == "SET_ACTIVITY")
+            .map(|command| command["args"]["activity"].clone())
+            .collect()
+    }
+
+    pub fn last_activity(&self) -> Option<Value> {
+        self.activities().pop()
+    }
+
+    pub fn subscriptions(&self) -> Vec<String> {
+        self.state.lock().subscriptions.clone()
+    }
+
+    /// Answers later subscriptions to `event` with an `ERROR`, like Discord does for events the
+    /// application isn't approved or authorized for.
+    pub fn reject_subscription(&self, event: &str) {
+        self.state.lock().rejected_events.push(event.to_string());
+    }
+
+    pub fn dispatch(&self, event: &str, data: Value) {
+        self.state.lock().dispatch(event, data);
+    }
+
+    pub fn inject_join_game(&self, secret: &str) {
+        self.dispatch("ACTIVITY_JOIN", json!({ "secret": secret }));
+    }
+
+    pub fn inject_spectate_game(&self, secret: &str) {
+        self.dispatch("ACTIVITY_SPECTATE", json!({ "secret": secret }));
+    }
+
+    pub fn inject_join_request(&self, user: &User) {
+        self.dispatch("ACTIVITY_JOIN_REQUEST", json!({ "user": user }));
+    }
+
+    /// Sends a PING frame, which the client should answer with a PONG.
+    pub fn send_ping(&self) {
+        self.state.lock().queue(opcode::PING, &json!({}));
+    }
+
+    /// Sends a CLOSE frame, as Discord does before dropping a connection it rejects.
+    pub fn send_close(&self, code: u32, message: &str) {
+        self.state
+            .lock()
+            .queue(opcode::CLOSE, &json!({ "code": code, "message": message }));
+    }
+
+    /// Polls `condition` until it holds or `timeout` elapses, returning whether it held.
+    pub fn wait_until(&self, timeout: Duration, mut condition: impl FnMut(&Self) -> bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if condition(self) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+impl Connector for MockDiscord {
+    type Transport = MockTransport;
+
+    fn connect(&mut self, _config: &IpcConfig) -> io::Result<MockTransport> {
+        let mut state = self.state.lock();
+        if !state.available {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        state.generation += 1;
+        state.connected = true;
+        state.outgoing.clear();
+        Ok(MockTransport {
+            state: Arc::clone(&self.state),
+            generation: state.generation,
+            incoming: Vec::new(),
+        })
+    }
+}
+
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+    generation: u64,
+    incoming: Vec<u8>,
+}
+
+impl MockTransport {
+    fn is_current(&self, state: &MockState) -> bool {
+        state.connected && state.generation == self.generation
+    }
+
+    #[cfg(target_family = "unix")]
+    fn hang_up(&self) {
+        let mut state = self.state.lock();
+        if self.is_current(&state) {
+            state.connected = false;
+        }
+    }
+}
+
+impl Read for MockTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.lock();
+        if !self.is_current(&state) {
+            return Ok(0);
+        }
+        if state.outgoing.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let len = buf.len().min(state.outgoing.len());
+        for (dst, src) in buf.iter_mut().zip(state.outgoing.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for MockTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock();
+        if !self.is_current(&state) {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        self.incoming.extend_from_slice(buf);
+        while self.incoming.len() >= 8 {
+            let opcode = u32::from_le_bytes(self.incoming[0..4].try_into().unwrap());
+            let len = u32::from_le_bytes(self.incoming[4..8].try_into().unwrap()) as usize;
+            if self.incoming.len() < 8 + len {
+                break;
+            }
+            let frame = self.incoming.drain(..8 + len).collect::<Vec<_>>();
+            state.receive(opcode, &frame[8..]);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for MockTransport {}
+
+/// A [`MockDiscord`] served over a real Unix socket with Discord's IPC framing, for testing
+/// through the same connection path as the real client, e.g. by pointing `DISCORD_IPC_PATH` or
+/// [`IpcConfig::path`] at [`path`](Self::path).
+///
+/// Scripting goes through the [`MockDiscord`] it dereferences to; connection attempts are
+/// refused while it's unavailable. The socket is removed when the server is dropped.
+#[cfg(target_family = "unix")]
+pub struct FakeDiscordServer {
+    mock: MockDiscord,
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[cfg(target_family = "unix")]
+impl FakeDiscordServer {
+    pub fn bind(path: impl Into<PathBuf>, mock: MockDiscord) -> io::Result<Self> {
+        let path = path.into();
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name("Fake Discord server".to_string())
+            .spawn({
+                let mock = mock.clone();
+                let stopped = Arc::clone(&stopped);
+                move || serve(listener, mock, &stopped)
+            })?;
+        Ok(FakeDiscordServer {
+            mock,
+            path,
+            stopped,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A config connecting straight to this server.
+    pub fn ipc_config(&self) -> IpcConfig {
+        IpcConfig {
+            path: Some(self.path.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Deref for FakeDiscordServer {
+    type Target = MockDiscord;
+
+    fn deref(&self) -> &MockDiscord {
+        &self.mock
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for FakeDiscordServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Copies bytes both ways between each accepted stream and a transport of `mock`, until either
+/// side hangs up.
+#[cfg(target_family = "unix")]
+fn serve(listener: UnixListener, mut mock: MockDiscord, stopped: &AtomicBool) {
+    let mut connections = Vec::<(UnixStream, MockTransport)>::new();
+    let mut buf = [0; 4096];
+    while !stopped.load(Ordering::Relaxed) {
+        while let Ok((stream, _)) = listener.accept() {
+            if stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+            // Refused connections are dropped right away, like a missing socket would fail
+            if let Ok(transport) = mock.connect(&IpcConfig::default()) {
+                connections.push((stream, transport));
+            }
+        }
+        connections.retain_mut(|(stream, transport)| {
+            let open = pump(stream, transport, &mut buf).unwrap_or(false);
+            if !open {
+                transport.hang_up();
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            open
+        });
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn pump(
+    stream: &mut UnixStream,
+    transport: &mut MockTransport,
+    buf: &mut [u8],
+) -> io::Result<bool> {
+    loop {
+        match stream.read(buf) {
+            Ok(0) => return Ok(false),
+            Ok(len) => transport.write_all(&buf[..len])?,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => return Err(err),
+        }
+    }
+    loop {
+        match transport.read(buf) {
+            Ok(0) => return Ok(false),
+            Ok(len) => {
+                // The client reads continuously, so a short blocking write is fine here
+                stream.set_nonblocking(false)?;
+                let result = stream.write_all(&buf[..len]);
+                stream.set_nonblocking(true)?;
+                result?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockDiscord, MockTransport};
+    use crate::{
+        ConnectionStatus, Connector, DisconnectReason, ErrorCode, EventHandlers, IpcConfig,
+        JoinReply, Presence, Rpc, RpcClient, Snowflake, Transport, User,
+    };
+    use parking_lot::Mutex;
+    use serde_json::json;
+    use std::{
+        io::{self, Read, Write},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn connect(handlers: EventHandlers) -> (Rpc, MockDiscord) {
+        let mock = MockDiscord::default();
+        let rpc = Rpc::with_connector("1".to_string(), handlers, false, mock.clone());
+        assert!(mock.wait_until(TIMEOUT, MockDiscord::is_connected));
+        (rpc, mock)
+    }
+
+    #[test]
+    fn records_set_activity() {
+        let (mut rpc, mock) = connect(EventHandlers::default());
+        let presence = Presence::builder()
+            .state("In a match")
+            .details("Ranked")
+            .build()
+            .unwrap();
+        let mut receipt = rpc.update_presence(Some(&presence));
+        assert!(receipt.wait_timeout(TIMEOUT).unwrap().is_ok());
+
+        let command = mock.commands().pop().unwrap();
+        assert_eq!(command["cmd"], "SET_ACTIVITY");
+        assert_eq!(command["args"]["pid"], std::process::id());
+        assert_eq!(
+            mock.last_activity().unwrap(),
+            json!({ "type": 0, "state": "In a match", "details": "Ranked" }),
+        );
+
+        let mut receipt = rpc.update_presence(None);
+        assert!(receipt.wait_timeout(TIMEOUT).unwrap().is_ok());
+        assert_eq!(mock.last_activity(), Some(json!(null)));
+    }
+
+    #[test]
+    fn resolves_superseded_presences() {
+        let (mut rpc, mock) = connect(EventHandlers::default());
+        rpc.set_presence_rate_limit(None);
+        let mut receipts = (0..20)
+            .map(|i| {
+                let presence = Presence::builder().state(format!("State {}", i)).build();
+                rpc.update_presence(Some(&presence.unwrap()))
+            })
+            .collect::<Vec<_>>();
+        // Superseded updates fail with NO_RESPONSE if they were never sent, but all resolve
+        for receipt in &mut receipts {
+            assert!(receipt.wait_timeout(TIMEOUT).is_some());
+        }
+        assert!(receipts.last_mut().unwrap().try_get().unwrap().is_ok());
+        assert_eq!(mock.last_activity().unwrap()["state"], "State 19");
+
+        let mut nonces = mock
+            .commands()
+            .iter()
+            .filter(|command| command["cmd"] == "SET_ACTIVITY")
+            .map(|command| command["nonce"].to_string())
+            .collect::<Vec<_>>();
+        let len = nonces.len();
+        nonces.sort();
+        nonces.dedup();
+        assert_eq!(nonces.len(), len);
+    }
+
+    #[test]
+    fn resolves_receipts() {
+        let (mut rpc, mock) = connect(EventHandlers::default());
+        let mut receipt = rpc.send_command("GET_GUILDS", json!({}));
+        assert_eq!(receipt.wait_timeout(TIMEOUT).unwrap().unwrap(), json!({}));
+
+        mock.set_frozen(true);
+        let mut receipt = rpc.send_command("GET_GUILDS", json!({}));
+        assert!(receipt.wait_timeout(Duration::from_millis(100)).is_none());
+        mock.disconnect();
+        let err = receipt.wait_timeout(TIMEOUT).unwrap().unwrap_err();
+        assert_eq!(err.code, ErrorCode::NoResponse);
+    }
+
+    #[test]
+    fn resolves_join_replies_through_rpc_client() {
+        let (mut rpc, mock) = connect(EventHandlers::default());
+        let deadline = Instant::now() + TIMEOUT;
+        while !matches!(rpc.status(), ConnectionStatus::Connected { .. })
+            && Instant::now() < deadline
+        {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+        let client: &mut dyn RpcClient = &mut rpc;
+        let mut receipt = client.reply_to_join_request(Snowflake::from(42), JoinReply::Accept);
+        assert!(receipt.wait_timeout(TIMEOUT).unwrap().is_ok());
+        assert_eq!(
+            mock.commands().last().unwrap()["cmd"],
+            "SEND_ACTIVITY_JOIN_INVITE"
+        );
+    }
+
+    /// Panics on reads once `crash` is set, taking the IO thread down.
+    #[derive(Clone)]
+    struct CrashingConnector {
+        mock: MockDiscord,
+        crash: Arc<AtomicBool>,
+    }
+
+    struct CrashingTransport {
+        transport: MockTransport,
+        crash: Arc<AtomicBool>,
+    }
+
+    impl Connector for CrashingConnector {
+        type Transport = CrashingTransport;
+
+        fn connect(&mut self, config: &IpcConfig) -> io::Result<CrashingTransport> {
+            Ok(CrashingTransport {
+                transport: self.mock.connect(config)?,
+                crash: Arc::clone(&self.crash),
+            })
+        }
+    }
+
+    impl Read for CrashingTransport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.crash.swap(false, Ordering::Relaxed) {
+                panic!("IO thread crashed on purpose");
+            }
+            self.transport.read(buf)
+        }
+    }
+
+    impl Write for CrashingTransport {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.transport.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.transport.flush()
+        }
+    }
+
+    impl Transport for CrashingTransport {}
+
+    #[test]
+    fn resolves_receipts_after_io_thread_panics() {
+        let restarted = Arc::new(AtomicBool::new(false));
+        let handlers = EventHandlers {
+            io_thread_restarted: Some(Box::new({
+                let restarted = Arc::clone(&restarted);
+                move || restarted.store(true, Ordering::Relaxed)
+            })),
+            ..Default::default()
+        };
+        let mock = MockDiscord::default();
+        let crash = Arc::new(AtomicBool::new(false));
+        let connector = CrashingConnector {
+            mock: mock.clone(),
+            crash: Arc::clone(&crash),
+        };
+        let mut rpc = Rpc::with_connector("1".to_string(), handlers, false, connector);
+        assert!(mock.wait_until(TIMEOUT, MockDiscord::is_connected));
+
+        // Never answered, so the receipt is still pending when the thread dies
+        mock.set_frozen(true);
+        let mut receipt = rpc.send_command("GET_GUILDS", json!({}));
+        crash.store(true, Ordering::Relaxed);
+        let deadline = Instant::now() + TIMEOUT;
+        while receipt.try_get().is_none() && Instant::now() < deadline {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+        assert!(restarted.load(Ordering::Relaxed));
+        let err = receipt.try_get().unwrap().unwrap_err();
+        assert_eq!(err.code, ErrorCode::NoResponse);
+
+        // The restarted thread reconnects and serves new commands
+        mock.set_frozen(false);
+        let mut receipt = rpc.send_command("GET_GUILDS", json!({}));
+        assert!(receipt.wait_timeout(TIMEOUT).unwrap().is_ok());
+    }
+
+    #[test]
+    fn dispatches_injected_events() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let handlers = EventHandlers {
+            join_game: Some(Box::new({
+                let received = Arc::clone(&received);
+                move |secret| received.lock().push(format!("join {}", secret))
+            })),
+            spectate_game: Some(Box::new({
+                let received = Arc::clone(&received);
+                move |secret| received.lock().push(format!("spectate {}", secret))
+            })),
+            join_request: Some(Box::new({
+                let received = Arc::clone(&received);
+                move |user: User| received.lock().push(format!("request {}", user.username))
+            })),
+            ..Default::default()
+        };
+        let (mut rpc, mock) = connect(handlers);
+        assert!(mock.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 3));
+
+        let user =
+            serde_json::from_value::<User>(json!({ "id": "42", "username": "friend" })).unwrap();
+        mock.inject_join_game("join-secret");
+        mock.inject_spectate_game("spectate-secret");
+        mock.inject_join_request(&user);
+        let deadline = Instant::now() + TIMEOUT;
+        while received.lock().len() < 3 && Instant::now() < deadline {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+        assert_eq!(
+            *received.lock(),
+            [
+                "join join-secret",
+                "spectate spectate-secret",
+                "request friend"
+            ],
+        );
+    }
+
+    #[test]
+    fn reconnects_after_app_id_change() {
+        let reasons = Arc::new(Mutex::new(Vec::new()));
+        let handlers = EventHandlers {
+            disconnect: Some(Box::new({
+                let reasons = Arc::clone(&reasons);
+                move |reason| reasons.lock().push(reason)
+            })),
+            ..Default::default()
+        };
+        let (mut rpc, mock) = connect(handlers);
+        rpc.set_app_id("2".to_string());
+        let deadline = Instant::now() + TIMEOUT;
+        while reasons.lock().is_empty() && Instant::now() < deadline {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+        let reason = reasons.lock().pop().unwrap();
+        assert!(matches!(reason, DisconnectReason::AppIdChanged));
+        assert!(reason.is_recoverable());
+        assert!(mock.wait_until(TIMEOUT, MockDiscord::is_connected));
+    }
+
+    #[test]
+    fn reconnects_after_being_reenabled() {
+        let reasons = Arc::new(Mutex::new(Vec::new()));
+        let handlers = EventHandlers {
+            disconnect: Some(Box::new({
+                let reasons = Arc::clone(&reasons);
+                move |reason| reasons.lock().push(reason)
+            })),
+            ..Default::default()
+        };
+        let (mut rpc, mock) = connect(handlers);
+        rpc.set_enabled(false);
+        let deadline = Instant::now() + TIMEOUT;
+        while reasons.lock().is_empty() && Instant::now() < deadline {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+
+        // Commands fail while disabled, and the disconnection is only reported once
+        let err = rpc
+            .send_command("GET_GUILDS", json!({}))
+            .wait_timeout(TIMEOUT)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::NoResponse);
+        let deadline = Instant::now() + Duration::from_millis(1500);
+        while Instant::now() < deadline {
+            rpc.check_events_blocking(Some(Duration::from_millis(100)));
+        }
+        {
+            let reasons = reasons.lock();
+            assert_eq!(reasons.len(), 1);
+            assert!(matches!(reasons[0], DisconnectReason::Disabled));
+            assert!(reasons[0].is_recoverable());
+        }
+
+        mock.disconnect();
+        rpc.set_enabled(true);
+        assert!(mock.wait_until(TIMEOUT, MockDiscord::is_connected));
+    }
+}
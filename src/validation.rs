@@ -0,0 +1,467 @@
+use super::{limits, Presence};
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresenceError {
+    TooShort {
+        field: &'static str,
+        min_len: usize,
+    },
+    TooLong {
+        field: &'static str,
+        max_len: usize,
+    },
+    IncompletePartySize,
+    PartySizeExceedsMax {
+        size: u32,
+        max: u32,
+    },
+    SecretWithoutParty {
+        field: &'static str,
+    },
+    EndBeforeStart,
+    TooManyButtons {
+        len: usize,
+        max_len: usize,
+    },
+    FrameTooLarge {
+        len: usize,
+        max_len: usize,
+    },
+    WrongType {
+        field: &'static str,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for PresenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresenceError::TooShort { field, min_len } => write!(
+                f,
+                "presence field `{}` must be at least {} characters long",
+                field, min_len
+            ),
+            PresenceError::TooLong { field, max_len } => write!(
+                f,
+                "presence field `{}` must be at most {} characters long",
+                field, max_len
+            ),
+            PresenceError::IncompletePartySize => {
+                f.write_str("party size and max must be set together")
+            }
+            PresenceError::PartySizeExceedsMax { size, max } => {
+                write!(f, "party size {} exceeds its maximum {}", size, max)
+            }
+            PresenceError::SecretWithoutParty { field } => {
+                write!(f, "secret `{}` requires a party ID", field)
+            }
+            PresenceError::EndBeforeStart => f.write_str("end timestamp is before the start"),
+            PresenceError::TooManyButtons { len, max_len } => {
+                write!(f, "{} buttons exceed the maximum of {}", len, max_len)
+            }
+            PresenceError::FrameTooLarge { len, max_len } => write!(
+                f,
+                "frame of {} bytes exceeds the maximum of {} bytes",
+                len, max_len
+            ),
+            PresenceError::WrongType { field, expected } => {
+                write!(f, "presence field `{}` must be {}", field, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PresenceError {}
+
+fn check_len(
+    field: &'static str,
+    value: &str,
+    min_len: usize,
+    max_len: usize,
+) -> Result<(), PresenceError> {
+    let len = value.chars().count();
+    if len < min_len {
+        return Err(PresenceError::TooShort { field, min_len });
+    }
+    if len > max_len {
+        return Err(PresenceError::TooLong { field, max_len });
+    }
+    Ok(())
+}
+
+fn check_text(
+    field: &'static str,
+    value: &Option<String>,
+    min_len: usize,
+    max_len: usize,
+) -> Result<(), PresenceError> {
+    match value {
+        Some(value) => check_len(field, value, min_len, max_len),
+        None => Ok(()),
+    }
+}
+
+pub fn validate_presence(presence: &Presence) -> Result<(), PresenceError> {
+    check_text("state", &presence.state, limits::TEXT_MIN, limits::TEXT)?;
+    check_text("details", &presence.details, limits::TEXT_MIN, limits::TEXT)?;
+
+    if let Some(timestamps) = &presence.timestamps {
+        if let (Some(start), Some(end)) = (timestamps.start, timestamps.end) {
+            if end.to_system_time() < start.to_system_time() {
+                return Err(PresenceError::EndBeforeStart);
+            }
+        }
+    }
+
+    if let Some(images) = &presence.images {
+        check_text("large_image", &images.large_key, 1, limits::ASSET_KEY)?;
+        check_text(
+            "large_text",
+            &images.large_text,
+            limits::TEXT_MIN,
+            limits::TEXT,
+        )?;
+        check_text("small_image", &images.small_key, 1, limits::ASSET_KEY)?;
+        check_text(
+            "small_text",
+            &images.small_text,
+            limits::TEXT_MIN,
+            limits::TEXT,
+        )?;
+    }
+
+    let party_id = presence.party.as_ref().and_then(|party| party.id.as_ref());
+    if let Some(party) = &presence.party {
+        check_text("party_id", &party.id, 1, limits::PARTY_ID)?;
+        match (party.size, party.max) {
+            (Some(size), Some(max)) if size > max => {
+                return Err(PresenceError::PartySizeExceedsMax { size, max })
+            }
+            (Some(_), None) | (None, Some(_)) => return Err(PresenceError::IncompletePartySize),
+            _ => {}
+        }
+    }
+
+    if let Some(secrets) = &presence.secrets {
+        check_text("match_secret", &secrets.match_, 1, limits::SECRET)?;
+        check_text("join_secret", &secrets.join, 1, limits::SECRET)?;
+        check_text("spectate_secret", &secrets.spectate, 1, limits::SECRET)?;
+        if party_id.is_none() {
+            if secrets.join.is_some() {
+                return Err(PresenceError::SecretWithoutParty {
+                    field: "join_secret",
+                });
+            }
+            if secrets.spectate.is_some() {
+                return Err(PresenceError::SecretWithoutParty {
+                    field: "spectate_secret",
+                });
+            }
+        }
+    }
+
+    if presence.buttons.len() > limits::BUTTONS {
+        return Err(PresenceError::TooManyButtons {
+            len: presence.buttons.len(),
+            max_len: limits::BUTTONS,
+        });
+    }
+    for button in &presence.buttons {
+        check_len("button_label", &button.label, 1, limits::BUTTON_LABEL)?;
+        check_len("button_url", &button.url, 1, limits::BUTTON_URL)?;
+    }
+
+    Ok(())
+}
+
+pub fn validate_frame(frame: &[u8]) -> Result<(), PresenceError> {
+    if frame.len() > limits::FRAME {
+        return Err(PresenceError::FrameTooLarge {
+            len: frame.len(),
+            max_len: limits::FRAME,
+        });
+    }
+    Ok(())
+}
+
+fn json_text<'a>(
+    field: &'static str,
+    value: Option<&'a Value>,
+    min_len: usize,
+    max_len: usize,
+) -> Result<Option<&'a str>, PresenceError> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => {
+            check_len(field, value, min_len, max_len).map(|_| Some(&**value))
+        }
+        Some(_) => Err(PresenceError::WrongType {
+            field,
+            expected: "a string",
+        }),
+    }
+}
+
+fn json_object<'a>(
+    field: &'static str,
+    value: Option<&'a Value>,
+) -> Result<Option<&'a serde_json::Map<String, Value>>, PresenceError> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Object(value)) => Ok(Some(value)),
+        Some(_) => Err(PresenceError::WrongType {
+            field,
+            expected: "an object",
+        }),
+    }
+}
+
+fn json_u64(field: &'static str, value: Option<&Value>) -> Result<Option<u64>, PresenceError> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or(PresenceError::WrongType {
+            field,
+            expected: "a non-negative integer",
+        }),
+    }
+}
+
+fn json_bool(field: &'static str, value: Option<&Value>) -> Result<(), PresenceError> {
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(_)) => Ok(()),
+        Some(_) => Err(PresenceError::WrongType {
+            field,
+            expected: "a boolean",
+        }),
+    }
+}
+
+/// Checks an activity given as raw JSON against the same limits as [`validate_presence`], using
+/// the field layout Discord expects in `SET_ACTIVITY`; fields it doesn't know are left alone.
+pub fn validate_activity_json(activity: &Value) -> Result<(), PresenceError> {
+    let activity = json_object("activity", Some(activity))?.ok_or(PresenceError::WrongType {
+        field: "activity",
+        expected: "an object",
+    })?;
+    json_text(
+        "state",
+        activity.get("state"),
+        limits::TEXT_MIN,
+        limits::TEXT,
+    )?;
+    json_text(
+        "details",
+        activity.get("details"),
+        limits::TEXT_MIN,
+        limits::TEXT,
+    )?;
+
+    if let Some(timestamps) = json_object("timestamps", activity.get("timestamps"))? {
+        let start = json_u64("start", timestamps.get("start"))?;
+        let end = json_u64("end", timestamps.get("end"))?;
+        if let (Some(start), Some(end)) = (start, end) {
+            if end < start {
+                return Err(PresenceError::EndBeforeStart);
+            }
+        }
+    }
+
+    if let Some(assets) = json_object("assets", activity.get("assets"))? {
+        json_text(
+            "large_image",
+            assets.get("large_image"),
+            1,
+            limits::ASSET_KEY,
+        )?;
+        json_text(
+            "large_text",
+            assets.get("large_text"),
+            limits::TEXT_MIN,
+            limits::TEXT,
+        )?;
+        json_text(
+            "small_image",
+            assets.get("small_image"),
+            1,
+            limits::ASSET_KEY,
+        )?;
+        json_text(
+            "small_text",
+            assets.get("small_text"),
+            limits::TEXT_MIN,
+            limits::TEXT,
+        )?;
+    }
+
+    let mut party_id = None;
+    if let Some(party) = json_object("party", activity.get("party"))? {
+        party_id = json_text("party_id", party.get("id"), 1, limits::PARTY_ID)?;
+        match party.get("size") {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(size)) if size.len() == 2 => {
+                let current = json_u64("party_size", size.first())?.unwrap_or_default();
+                let max = json_u64("party_max", size.get(1))?.unwrap_or_default();
+                if current > max {
+                    return Err(PresenceError::PartySizeExceedsMax {
+                        size: current as u32,
+                        max: max as u32,
+                    });
+                }
+            }
+            Some(_) => return Err(PresenceError::IncompletePartySize),
+        }
+    }
+
+    if let Some(secrets) = json_object("secrets", activity.get("secrets"))? {
+        json_text("match_secret", secrets.get("match"), 1, limits::SECRET)?;
+        let join = json_text("join_secret", secrets.get("join"), 1, limits::SECRET)?;
+        let spectate = json_text(
+            "spectate_secret",
+            secrets.get("spectate"),
+            1,
+            limits::SECRET,
+        )?;
+        if party_id.is_none() {
+            if join.is_some() {
+                return Err(PresenceError::SecretWithoutParty {
+                    field: "join_secret",
+                });
+            }
+            if spectate.is_some() {
+                return Err(PresenceError::SecretWithoutParty {
+                    field: "spectate_secret",
+                });
+            }
+        }
+    }
+
+    json_bool("instance", activity.get("instance"))?;
+    json_u64("flags", activity.get("flags"))?;
+    match activity.get("supported_platforms") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(platforms)) if platforms.iter().all(Value::is_string) => {}
+        Some(_) => {
+            return Err(PresenceError::WrongType {
+                field: "supported_platforms",
+                expected: "an array of strings",
+            })
+        }
+    }
+
+    match activity.get("buttons") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(buttons)) => {
+            if buttons.len() > limits::BUTTONS {
+                return Err(PresenceError::TooManyButtons {
+                    len: buttons.len(),
+                    max_len: limits::BUTTONS,
+                });
+            }
+            for button in buttons {
+                let button =
+                    json_object("button", Some(button))?.ok_or(PresenceError::WrongType {
+                        field: "button",
+                        expected: "an object",
+                    })?;
+                for (field, key, max_len) in [
+                    ("button_label", "label", limits::BUTTON_LABEL),
+                    ("button_url", "url", limits::BUTTON_URL),
+                ] {
+                    if json_text(field, button.get(key), 1, max_len)?.is_none() {
+                        return Err(PresenceError::TooShort { field, min_len: 1 });
+                    }
+                }
+            }
+        }
+        Some(_) => {
+            return Err(PresenceError::WrongType {
+                field: "buttons",
+                expected: "an array",
+            })
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Party, PartyPrivacy, Timestamps};
+    use serde_json::json;
+    use std::time::{Duration, SystemTime};
+
+    fn full_presence() -> Presence {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        Presence::builder()
+            .state("In a match")
+            .details("Ranked")
+            .timestamps(Timestamps {
+                start: Some(start.into()),
+                end: Some((start + Duration::from_secs(600)).into()),
+                ..Default::default()
+            })
+            .large_image("map")
+            .large_text("Dust")
+            .small_image("rank")
+            .small_text("Gold")
+            .party_id("party")
+            .party_size(2, 4)
+            .party_privacy(PartyPrivacy::Public)
+            .match_secret("match")
+            .join_secret("join")
+            .spectate_secret("spectate")
+            .button("Website", "https://example.com")
+            .instance(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_serialized_presences() {
+        let presence = full_presence();
+        let activity = serde_json::to_value(&presence).unwrap();
+        assert_eq!(validate_activity_json(&activity), Ok(()));
+        assert_eq!(activity["assets"]["large_image"], "map");
+        assert_eq!(
+            activity["party"],
+            json!({ "id": "party", "size": [2, 4], "privacy": 1 })
+        );
+        assert_eq!(
+            serde_json::from_value::<Presence>(activity).unwrap(),
+            presence
+        );
+    }
+
+    #[test]
+    fn reports_the_same_errors_as_presences() {
+        let mut presence = full_presence();
+        presence.party.as_mut().unwrap().size = Some(5);
+        let activity = serde_json::to_value(&presence).unwrap();
+        let err = PresenceError::PartySizeExceedsMax { size: 5, max: 4 };
+        assert_eq!(validate_presence(&presence), Err(err.clone()));
+        assert_eq!(validate_activity_json(&activity), Err(err));
+    }
+
+    #[test]
+    fn reports_oversized_parties() {
+        let presence = Presence {
+            party: Some(Party::of("party").size(5, 4)),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_presence(&presence),
+            Err(PresenceError::PartySizeExceedsMax { size: 5, max: 4 })
+        );
+    }
+
+    #[test]
+    fn rejects_lone_party_sizes() {
+        let activity = json!({ "party": { "id": "party", "size": 2 } });
+        assert_eq!(
+            validate_activity_json(&activity),
+            Err(PresenceError::IncompletePartySize)
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use super::{Pan, Snowflake};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VoiceIoSettings {
+    pub available_devices: Vec<AudioDevice>,
+    pub device_id: String,
+    pub volume: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VoiceModeType {
+    #[default]
+    VoiceActivity,
+    PushToTalk,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum ShortcutKeyType {
+    KeyboardKey,
+    MouseButton,
+    KeyboardModifierKey,
+    GamepadButton,
+    Unknown(u8),
+}
+
+impl From<u8> for ShortcutKeyType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => ShortcutKeyType::KeyboardKey,
+            1 => ShortcutKeyType::MouseButton,
+            2 => ShortcutKeyType::KeyboardModifierKey,
+            3 => ShortcutKeyType::GamepadButton,
+            _ => ShortcutKeyType::Unknown(value),
+        }
+    }
+}
+
+impl From<ShortcutKeyType> for u8 {
+    fn from(value: ShortcutKeyType) -> Self {
+        match value {
+            ShortcutKeyType::KeyboardKey => 0,
+            ShortcutKeyType::MouseButton => 1,
+            ShortcutKeyType::KeyboardModifierKey => 2,
+            ShortcutKeyType::GamepadButton => 3,
+            ShortcutKeyType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ShortcutKeyCombo {
+    #[serde(rename = "type")]
+    pub kind: ShortcutKeyType,
+    pub code: u32,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VoiceMode {
+    #[serde(rename = "type")]
+    pub kind: VoiceModeType,
+    pub auto_threshold: bool,
+    pub threshold: f32,
+    pub shortcut: Vec<ShortcutKeyCombo>,
+    pub delay: f32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VoiceSettings {
+    pub input: VoiceIoSettings,
+    pub output: VoiceIoSettings,
+    pub mode: VoiceMode,
+    pub automatic_gain_control: bool,
+    pub echo_cancellation: bool,
+    pub noise_suppression: bool,
+    pub qos: bool,
+    pub silence_warning: bool,
+    pub deaf: bool,
+    pub mute: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VoiceIoSettingsUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+}
+
+/// A partial change to the user's voice settings; fields left as `None` are not modified.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VoiceSettingsUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<VoiceIoSettingsUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<VoiceIoSettingsUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<VoiceMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub automatic_gain_control: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub echo_cancellation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noise_suppression: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qos: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub silence_warning: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deaf: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mute: Option<bool>,
+}
+
+/// Local voice settings for another user; fields left as `None` are not modified.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UserVoiceSettings {
+    pub user_id: Snowflake,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pan: Option<Pan>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mute: Option<bool>,
+}
+
+impl UserVoiceSettings {
+    pub fn new(user_id: Snowflake) -> Self {
+        UserVoiceSettings {
+            user_id,
+            pan: None,
+            volume: None,
+            mute: None,
+        }
+    }
+}
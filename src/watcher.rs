@@ -0,0 +1,61 @@
+use super::{Error, ErrorCode};
+use serde_json::Value;
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls a JSON activity file's modification time, reloading it whenever it changes.
+///
+/// Only JSON is read: it's the format Discord takes activities in, so the file is sent as is
+/// without another parser to depend on. For the same reason the file is polled rather than
+/// watched through OS notifications; Discord rate limits presence updates well below once a
+/// second anyway.
+pub struct PresenceFileWatcher {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+    next_check: Instant,
+}
+
+impl PresenceFileWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        PresenceFileWatcher {
+            path,
+            modified: None,
+            next_check: Instant::now(),
+        }
+    }
+
+    pub fn load(&mut self) -> Result<Value, Error> {
+        self.next_check = Instant::now() + CHECK_INTERVAL;
+        let load_failed = |err: &dyn std::fmt::Display| Error {
+            message: format!("Couldn't load {}: {}", self.path.display(), err),
+            code: ErrorCode::LoadFailed,
+        };
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|err| load_failed(&err))?;
+        let contents = fs::read(&self.path).map_err(|err| load_failed(&err))?;
+        // Only remember the file as loaded once it was read, so failed reads are retried
+        self.modified = Some(modified);
+        serde_json::from_slice(&contents).map_err(|err| load_failed(&err))
+    }
+
+    /// Reloads the file if it was modified since the last load, at most once per second.
+    pub fn poll(&mut self) -> Option<Result<Value, Error>> {
+        if Instant::now() < self.next_check {
+            return None;
+        }
+        self.next_check = Instant::now() + CHECK_INTERVAL;
+        let modified = fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        Some(self.load())
+    }
+}
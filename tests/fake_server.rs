@@ -0,0 +1,184 @@
+#![cfg(target_family = "unix")]
+
+use discord_rpc::{
+    testing::{FakeDiscordServer, MockDiscord},
+    BaseConnection, Connector, DisconnectReason, ErrorCode, EventHandlers, IpcConfig, Jitter,
+    Keepalive, ReconnectPolicy, Rpc, User,
+};
+use parking_lot::Mutex;
+use serde_json::json;
+use std::{
+    env, io,
+    path::PathBuf,
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connects to the fake server whatever the configured path is, so a running Discord client is
+/// never picked up instead.
+#[derive(Clone)]
+struct ServerConnector(PathBuf);
+
+impl Connector for ServerConnector {
+    type Transport = BaseConnection;
+
+    fn connect(&mut self, config: &IpcConfig) -> io::Result<BaseConnection> {
+        BaseConnection::open(&IpcConfig {
+            path: Some(self.0.clone()),
+            ..config.clone()
+        })
+    }
+}
+
+fn bind(name: &str, mock: MockDiscord) -> FakeDiscordServer {
+    let path = env::temp_dir().join(format!("discord-rpc-{}-{}", process::id(), name));
+    FakeDiscordServer::bind(path, mock).unwrap()
+}
+
+fn connect(server: &FakeDiscordServer, handlers: EventHandlers) -> Rpc {
+    let mut rpc = Rpc::with_connector(
+        "1".to_string(),
+        handlers,
+        false,
+        ServerConnector(server.path().to_path_buf()),
+    );
+    rpc.set_reconnect_policy(ReconnectPolicy {
+        min_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(100),
+        jitter: Jitter::None,
+        max_attempts: None,
+    });
+    assert!(server.wait_until(TIMEOUT, MockDiscord::is_connected));
+    rpc
+}
+
+/// Handlers recording every disconnection.
+fn recording_disconnects() -> (EventHandlers, Arc<Mutex<Vec<DisconnectReason>>>) {
+    let reasons = Arc::new(Mutex::new(Vec::new()));
+    let handlers = EventHandlers {
+        disconnect: Some(Box::new({
+            let reasons = Arc::clone(&reasons);
+            move |reason| reasons.lock().push(reason)
+        })),
+        ..Default::default()
+    };
+    (handlers, reasons)
+}
+
+fn wait_for_disconnect(rpc: &mut Rpc, reasons: &Mutex<Vec<DisconnectReason>>) -> DisconnectReason {
+    let deadline = Instant::now() + TIMEOUT;
+    while reasons.lock().is_empty() && Instant::now() < deadline {
+        rpc.check_events_blocking(Some(Duration::from_millis(100)));
+    }
+    reasons.lock().remove(0)
+}
+
+#[test]
+fn completes_handshake() {
+    let user = serde_json::from_value::<User>(json!({ "id": "42", "username": "player" })).unwrap();
+    let server = bind("handshake", MockDiscord::new(Some(user)));
+    let connected = Arc::new(Mutex::new(None));
+    let handlers = EventHandlers {
+        connect: Some(Box::new({
+            let connected = Arc::clone(&connected);
+            move |user| *connected.lock() = Some(user)
+        })),
+        ..Default::default()
+    };
+    let mut rpc = connect(&server, handlers);
+
+    let deadline = Instant::now() + TIMEOUT;
+    while connected.lock().is_none() && Instant::now() < deadline {
+        rpc.check_events_blocking(Some(Duration::from_millis(100)));
+    }
+    let user = connected.lock().take().unwrap().unwrap();
+    assert_eq!(user.username, "player");
+    assert_eq!(rpc.current_user().unwrap().username, "player");
+    assert!(rpc.server_config().is_some());
+}
+
+#[test]
+fn resubscribes_after_close() {
+    let server = bind("close", MockDiscord::default());
+    let (mut handlers, reasons) = recording_disconnects();
+    handlers.join_game = Some(Box::new(|_| {}));
+    let mut rpc = connect(&server, handlers);
+    assert!(server.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 1));
+
+    server.send_close(1000, "Restarting");
+    match wait_for_disconnect(&mut rpc, &reasons) {
+        DisconnectReason::Closed(Some(err)) => {
+            assert_eq!(err.code, ErrorCode::CloseNormal);
+            assert_eq!(err.message, "Restarting");
+        }
+        reason => panic!("unexpected disconnection: {:?}", reason),
+    }
+
+    // The fake keeps subscriptions across connections, so resubscribing adds another
+    assert!(server.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 2));
+    assert_eq!(server.subscriptions(), ["ACTIVITY_JOIN", "ACTIVITY_JOIN"]);
+    assert!(server.is_connected());
+}
+
+#[test]
+fn times_out_unanswered_pings() {
+    let server = bind("keepalive", MockDiscord::default());
+    let (handlers, reasons) = recording_disconnects();
+    let mut rpc = connect(&server, handlers);
+    rpc.set_keepalive(Some(Keepalive {
+        interval: Duration::from_millis(50),
+        timeout: Duration::from_millis(200),
+    }));
+
+    // Answered PINGs keep the connection open; the IO thread polls every 500ms at the least
+    let deadline = Instant::now() + Duration::from_millis(1500);
+    while Instant::now() < deadline {
+        rpc.check_events_blocking(Some(Duration::from_millis(100)));
+    }
+    assert!(reasons.lock().is_empty());
+    // The READY dispatch, then at least one PONG
+    assert!(rpc.stats().traffic.frames_received >= 2);
+
+    server.set_frozen(true);
+    let reason = wait_for_disconnect(&mut rpc, &reasons);
+    assert_eq!(reason.error().unwrap().code, ErrorCode::PingTimeout);
+    assert!(reason.is_recoverable());
+}
+
+#[test]
+fn rejects_oversized_frames() {
+    let server = bind("max-frame-size", MockDiscord::default());
+    let (mut handlers, reasons) = recording_disconnects();
+    let joined = Arc::new(Mutex::new(Vec::new()));
+    handlers.join_game = Some(Box::new({
+        let joined = Arc::clone(&joined);
+        move |secret| joined.lock().push(secret)
+    }));
+    let mut rpc = connect(&server, handlers);
+    assert!(server.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 1));
+
+    // The limit applies from the next connection on
+    rpc.set_ipc_config(IpcConfig {
+        max_frame_size: 256,
+        ..IpcConfig::default()
+    });
+    server.disconnect();
+    wait_for_disconnect(&mut rpc, &reasons);
+    assert!(server.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 2));
+
+    server.inject_join_game(&"a".repeat(256));
+    let reason = wait_for_disconnect(&mut rpc, &reasons);
+    assert_eq!(reason.error().unwrap().code, ErrorCode::ReadCorrupt);
+
+    // Frames within the limit still arrive once reconnected
+    assert!(server.wait_until(TIMEOUT, |mock| mock.subscriptions().len() == 3));
+    server.inject_join_game("secret");
+    let deadline = Instant::now() + TIMEOUT;
+    while joined.lock().is_empty() && Instant::now() < deadline {
+        rpc.check_events_blocking(Some(Duration::from_millis(100)));
+    }
+    assert_eq!(*joined.lock(), ["secret"]);
+}